@@ -0,0 +1,155 @@
+use aes;
+use hmac;
+use pkcs7;
+use rand::{thread_rng, Rng};
+use std::error;
+use std::fmt;
+use util;
+
+// HMAC-SHA1 tags
+pub const TAG_SIZE: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+  CBC,
+  CTR,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+  InvalidLength(usize),
+  BadPadding,
+  BadMac,
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Error::InvalidLength(length) => write!(f, "Invalid ciphertext length {}", length),
+      Error::BadPadding => write!(f, "Bad padding"),
+      Error::BadMac => write!(f, "Bad MAC"),
+    }
+  }
+}
+
+impl error::Error for Error {}
+
+fn cipher_mode(mode: Mode, iv: [u8; 16]) -> aes::CipherMode {
+  match mode {
+    Mode::CBC => aes::CipherMode::CBC(iv),
+    Mode::CTR => aes::CipherMode::CTR(iv),
+  }
+}
+
+// Encrypts under a fresh random IV, which leads the ciphertext. CBC pads the
+// plaintext first.
+pub fn encrypt(key: &[u8], mode: Mode, plaintext: &[u8]) -> Vec<u8> {
+  let iv: [u8; 16] = thread_rng().gen();
+  let plaintext = match mode {
+    Mode::CBC => pkcs7::pad(plaintext, 16),
+    Mode::CTR => plaintext.to_vec(),
+  };
+  let mut ciphertext = iv.to_vec();
+  ciphertext.extend(aes::perform(
+    &plaintext,
+    key,
+    aes::Operation::Encrypt,
+    cipher_mode(mode, iv),
+  ));
+  ciphertext
+}
+
+pub fn decrypt(key: &[u8], mode: Mode, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+  let is_valid_length = match mode {
+    Mode::CBC => ciphertext.len() >= 32 && ciphertext.len().is_multiple_of(16),
+    Mode::CTR => ciphertext.len() >= 16,
+  };
+  if !is_valid_length {
+    return Err(Error::InvalidLength(ciphertext.len()));
+  }
+  let (iv, ciphertext) = ciphertext.split_at(16);
+  let mut plaintext = aes::perform(
+    ciphertext,
+    key,
+    aes::Operation::Decrypt,
+    cipher_mode(mode, util::convert_to_fixed_array(iv)),
+  );
+  if let Mode::CBC = mode {
+    if !pkcs7::is_valid(&plaintext, 16) {
+      return Err(Error::BadPadding);
+    }
+    pkcs7::unpad_mut(&mut plaintext, 16);
+  }
+  Ok(plaintext)
+}
+
+pub fn mac(key: &[u8], message: &[u8]) -> Vec<u8> {
+  hmac::hmac_sha1(key, message)
+}
+
+// Compares tags without stopping at the first difference.
+pub fn tags_match(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len()
+    && a
+      .iter()
+      .zip(b.iter())
+      .fold(0, |accum, (x, y)| accum | (x ^ y))
+      == 0
+}
+
+// Encrypt-then-MAC: the tag covers the IV and the ciphertext, so nothing is
+// decrypted until it has been authenticated.
+pub struct EncryptThenMac {
+  encryption_key: Vec<u8>,
+  mac_key: Vec<u8>,
+  mode: Mode,
+}
+
+impl EncryptThenMac {
+  pub fn new(encryption_key: &[u8], mac_key: &[u8], mode: Mode) -> EncryptThenMac {
+    EncryptThenMac {
+      encryption_key: encryption_key.to_vec(),
+      mac_key: mac_key.to_vec(),
+      mode,
+    }
+  }
+
+  pub fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = encrypt(&self.encryption_key, self.mode, plaintext);
+    let tag = mac(&self.mac_key, &sealed);
+    sealed.extend(tag);
+    sealed
+  }
+
+  pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < TAG_SIZE {
+      return Err(Error::InvalidLength(sealed.len()));
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_SIZE);
+    if !tags_match(tag, &mac(&self.mac_key, ciphertext)) {
+      return Err(Error::BadMac);
+    }
+    decrypt(&self.encryption_key, self.mode, ciphertext)
+  }
+}
+
+#[test]
+fn encrypt_then_mac() {
+  let mut rng = thread_rng();
+  let encryption_key = util::gen_random_bytes(&mut rng, 16);
+  let mac_key = util::gen_random_bytes(&mut rng, 16);
+  for &mode in [Mode::CBC, Mode::CTR].iter() {
+    let aead = EncryptThenMac::new(&encryption_key, &mac_key, mode);
+    let plaintext = b"Attack at dawn, bring snacks".to_vec();
+    let sealed = aead.seal(&plaintext);
+    assert_eq!(Ok(plaintext), aead.open(&sealed));
+    for i in 0..sealed.len() {
+      let mut tampered = sealed.clone();
+      tampered[i] ^= 1;
+      assert_eq!(Err(Error::BadMac), aead.open(&tampered));
+    }
+    assert_eq!(Err(Error::InvalidLength(10)), aead.open(&sealed[..10]));
+    let other = EncryptThenMac::new(&mac_key, &encryption_key, mode);
+    assert_eq!(Err(Error::BadMac), other.open(&sealed));
+  }
+}
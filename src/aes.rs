@@ -1,3 +1,11 @@
+use aead;
+use cmac;
+use gf128;
+use modes::{self, BlockCipher, CTRCipherMode, CipherModeImpl};
+use pkcs7;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
 use std::ops::BitXor;
 use std::ops::BitXorAssign;
 use util;
@@ -7,7 +15,7 @@ const RCON: [u8; 11] = [
   0x8d, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36,
 ];
 
-const SBOX: [u8; 256] = [
+pub const SBOX: [u8; 256] = [
   0x63, 0x7C, 0x77, 0x7B, 0xF2, 0x6B, 0x6F, 0xC5, 0x30, 0x01, 0x67, 0x2B, 0xFE, 0xD7, 0xAB, 0x76,
   0xCA, 0x82, 0xC9, 0x7D, 0xFA, 0x59, 0x47, 0xF0, 0xAD, 0xD4, 0xA2, 0xAF, 0x9C, 0xA4, 0x72, 0xC0,
   0xB7, 0xFD, 0x93, 0x26, 0x36, 0x3F, 0xF7, 0xCC, 0x34, 0xA5, 0xE5, 0xF1, 0x71, 0xD8, 0x31, 0x15,
@@ -26,7 +34,7 @@ const SBOX: [u8; 256] = [
   0x8C, 0xA1, 0x89, 0x0D, 0xBF, 0xE6, 0x42, 0x68, 0x41, 0x99, 0x2D, 0x0F, 0xB0, 0x54, 0xBB, 0x16,
 ];
 
-const INV_SBOX: [u8; 256] = [
+pub const INV_SBOX: [u8; 256] = [
   0x52, 0x09, 0x6A, 0xD5, 0x30, 0x36, 0xA5, 0x38, 0xBF, 0x40, 0xA3, 0x9E, 0x81, 0xF3, 0xD7, 0xFB,
   0x7C, 0xE3, 0x39, 0x82, 0x9B, 0x2F, 0xFF, 0x87, 0x34, 0x8E, 0x43, 0x44, 0xC4, 0xDE, 0xE9, 0xCB,
   0x54, 0x7B, 0x94, 0x32, 0xA6, 0xC2, 0x23, 0x3D, 0xEE, 0x4C, 0x95, 0x0B, 0x42, 0xFA, 0xC3, 0x4E,
@@ -93,7 +101,7 @@ fn generate_four_bytes(
   t
 }
 
-fn expand_key(key: &[u8]) -> Vec<u8> {
+pub fn expand_key(key: &[u8]) -> Vec<u8> {
   let key_length = key.len();
   let (rounds, sbox_round, extra_expansions) = match key_length {
     16 => (10, false, 0),
@@ -180,6 +188,76 @@ fn expand_key_16() {
   );
 }
 
+// Runs the key schedule backwards from part of it to the key it was expanded
+// from. A key of n bytes needs n consecutive bytes of the schedule, so for
+// AES-192 and AES-256 the given bytes start at the key of the given round and
+// run on into the next one. recover_key_schedule gives the full schedule.
+pub fn invert_key_schedule(round_keys: &[u8], round: usize) -> Vec<u8> {
+  let key_length = round_keys.len();
+  let rounds = match key_length {
+    16 => 10,
+    24 => 12,
+    32 => 14,
+    len => panic!("Unsupported key length {}", len),
+  };
+  let words_per_key = key_length / 4;
+  let start = 4 * round;
+  assert!(
+    start + words_per_key <= 4 * (rounds + 1),
+    "Round {} is too late to recover a {} byte key from",
+    round,
+    key_length
+  );
+  let mut words = vec![[0; 4]; start + words_per_key];
+  for (i, chunk) in round_keys.chunks(4).enumerate() {
+    words[start + i] = to_four_byte_array(chunk);
+  }
+  // Each word is the xor of the one a key length before it with a function
+  // of the one just before it, so the schedule can be unwound a word at a
+  // time.
+  for i in (words_per_key..words.len()).rev() {
+    let mut t = words[i - 1];
+    if i % words_per_key == 0 {
+      t.rotate_left(1);
+      sbox(&mut t);
+      t[0] ^= RCON[i / words_per_key];
+    } else if words_per_key > 6 && i % words_per_key == 4 {
+      sbox(&mut t);
+    }
+    for (k, byte) in t.iter().enumerate() {
+      words[i - words_per_key][k] = words[i][k] ^ byte;
+    }
+  }
+  words[..words_per_key].concat()
+}
+
+// The whole expanded schedule that the given part of it comes from, with the
+// key itself at the start.
+pub fn recover_key_schedule(round_keys: &[u8], round: usize) -> Vec<u8> {
+  expand_key(&invert_key_schedule(round_keys, round))
+}
+
+#[test]
+fn test_invert_key_schedule() {
+  use util::parse_byte_string;
+  for key in [
+    "2b7e151628aed2a6abf7158809cf4f3c",
+    "8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b",
+    "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4",
+  ]
+  .iter()
+  {
+    let key = parse_byte_string(key);
+    let expanded_key = expand_key(&key);
+    for round in 0..(expanded_key.len() - key.len()) / 16 + 1 {
+      let start = 16 * round;
+      let round_keys = &expanded_key[start..start + key.len()];
+      assert_eq!(key, invert_key_schedule(round_keys, round));
+      assert_eq!(expanded_key, recover_key_schedule(round_keys, round));
+    }
+  }
+}
+
 #[test]
 fn expand_key_24() {
   assert_eq!(208, expand_key(&vec![0; 24]).len());
@@ -256,18 +334,20 @@ fn test_shift_rows_ident() {
 const COLUMN_MATRIX: [u8; 16] = [2, 3, 1, 1, 1, 2, 3, 1, 1, 1, 2, 3, 3, 1, 1, 2];
 const INV_COLUMN_MATRIX: [u8; 16] = [14, 11, 13, 9, 9, 14, 11, 13, 13, 9, 14, 11, 11, 13, 9, 14];
 
-fn gmul(mut a: u8, mut b: u8) -> u8 {
+pub const fn gmul(mut a: u8, mut b: u8) -> u8 {
   let mut p = 0;
-  for _ in 0..8 {
+  let mut i = 0;
+  while i < 8 {
     if (b & 0x1) != 0 {
-      p.bitxor_assign(a);
+      p ^= a;
     }
     let has_high_bit = (a & 0x80) == 0x80;
     a <<= 1;
     if has_high_bit {
-      a.bitxor_assign(0x1b);
+      a ^= 0x1b;
     }
     b >>= 1;
+    i += 1;
   }
   p
 }
@@ -335,62 +415,686 @@ fn inv_mix_columns(state: &mut [u8]) {
 pub enum CipherMode {
   ECB,
   CBC([u8; 16]),
+  // Initial counter block, incremented as a 128 bit big endian integer
+  CTR([u8; 16]),
+  // Cipher feedback over whole blocks and one byte at a time
+  CFB([u8; 16]),
+  CFB8([u8; 16]),
+  OFB([u8; 16]),
+  PCBC([u8; 16]),
+  // CBC with ciphertext stealing in the three orders of NIST SP 800-38A's
+  // addendum. CS3 is the one used by Kerberos.
+  CBCCS1([u8; 16]),
+  CBCCS2([u8; 16]),
+  CBCCS3([u8; 16]),
+  // Nonce and additional authenticated data. The 16 byte tag is appended to
+  // the ciphertext when encrypting and checked when decrypting.
+  GCM([u8; 12], Vec<u8>),
 }
 
-fn transform_chunk(chunk: &[u8], expanded_key: &[u8], operation: Operation) -> [u8; 16] {
-  const STATE_SIZE: usize = 16;
-  assert!(
-    chunk.len() == STATE_SIZE,
-    "Chunk size of {} is invalid; expected {}",
-    chunk.len(),
-    STATE_SIZE
-  );
-
-  let last_round = expanded_key.chunks(STATE_SIZE).count() - 1;
-  let mut state = util::convert_to_fixed_array(chunk);
-  match operation {
-    Operation::Encrypt => {
-      for (round, round_key) in expanded_key.chunks(STATE_SIZE).enumerate() {
-        match round {
-          0 => {
-            add_round_key(&mut state, round_key);
-          }
-          n if n != last_round => {
-            sbox(&mut state);
-            shift_rows(&mut state);
-            mix_columns(&mut state);
-            add_round_key(&mut state, round_key);
-          }
-          _ => {
-            sbox(&mut state);
-            shift_rows(&mut state);
-            add_round_key(&mut state, round_key);
+// Each entry is a column of MixColumns applied to the (inverse) S-box output,
+// rotated right by the given number of bytes for the rows below the first. A
+// whole round then becomes sixteen lookups and xors.
+const fn t_table(sbox: &[u8; 256], column: [u8; 4], rotation: u32) -> [u32; 256] {
+  let mut table = [0; 256];
+  let mut i = 0;
+  while i < 256 {
+    let s = sbox[i];
+    let entry = u32::from_be_bytes([
+      gmul(column[0], s),
+      gmul(column[1], s),
+      gmul(column[2], s),
+      gmul(column[3], s),
+    ]);
+    table[i] = entry.rotate_right(8 * rotation);
+    i += 1;
+  }
+  table
+}
+
+const TE: [[u32; 256]; 4] = [
+  t_table(&SBOX, [2, 1, 1, 3], 0),
+  t_table(&SBOX, [2, 1, 1, 3], 1),
+  t_table(&SBOX, [2, 1, 1, 3], 2),
+  t_table(&SBOX, [2, 1, 1, 3], 3),
+];
+
+const TD: [[u32; 256]; 4] = [
+  t_table(&INV_SBOX, [14, 9, 13, 11], 0),
+  t_table(&INV_SBOX, [14, 9, 13, 11], 1),
+  t_table(&INV_SBOX, [14, 9, 13, 11], 2),
+  t_table(&INV_SBOX, [14, 9, 13, 11], 3),
+];
+
+fn to_columns(bytes: &[u8; 16]) -> [u32; 4] {
+  let mut columns = [0; 4];
+  for (column, chunk) in columns.iter_mut().zip(bytes.chunks(4)) {
+    *column = u32::from_be_bytes(to_four_byte_array(chunk));
+  }
+  columns
+}
+
+fn from_columns(columns: &[u32; 4]) -> [u8; 16] {
+  let mut bytes = [0; 16];
+  for (chunk, column) in bytes.chunks_mut(4).zip(columns.iter()) {
+    chunk.copy_from_slice(&column.to_be_bytes());
+  }
+  bytes
+}
+
+// The row r byte of a column.
+fn row(column: u32, r: usize) -> usize {
+  (column >> (24 - 8 * r)) as usize & 0xff
+}
+
+// Runs the rounds of either direction. The columns feeding row r of output
+// column c are c + r * shift, which is all that differs between ShiftRows and
+// its inverse.
+fn t_table_rounds(
+  block: &[u8; 16],
+  round_keys: &[[u8; 16]],
+  tables: &[[u32; 256]; 4],
+  sbox: &[u8; 256],
+  shift: usize,
+) -> [u8; 16] {
+  let mut state = to_columns(block);
+  for (column, key) in state.iter_mut().zip(to_columns(&round_keys[0]).iter()) {
+    *column ^= key;
+  }
+  let last_round = round_keys.len() - 1;
+  for round_key in round_keys[1..last_round].iter() {
+    let key = to_columns(round_key);
+    let mut next = [0; 4];
+    for (c, column) in next.iter_mut().enumerate() {
+      *column = tables[0][row(state[c], 0)]
+        ^ tables[1][row(state[(c + shift) % 4], 1)]
+        ^ tables[2][row(state[(c + 2 * shift) % 4], 2)]
+        ^ tables[3][row(state[(c + 3 * shift) % 4], 3)]
+        ^ key[c];
+    }
+    state = next;
+  }
+  // The last round has no MixColumns, so only the S-box is needed.
+  let key = to_columns(&round_keys[last_round]);
+  let mut next = [0; 4];
+  for (c, column) in next.iter_mut().enumerate() {
+    *column = u32::from_be_bytes([
+      sbox[row(state[c], 0)],
+      sbox[row(state[(c + shift) % 4], 1)],
+      sbox[row(state[(c + 2 * shift) % 4], 2)],
+      sbox[row(state[(c + 3 * shift) % 4], 3)],
+    ]) ^ key[c];
+  }
+  from_columns(&next)
+}
+
+// A bitsliced state: bit j of plane i is bit i of byte j. Every operation
+// below is a fixed sequence of ands, xors and shifts on the planes, so neither
+// the time taken nor the memory touched depends on the data.
+type Planes = [u16; 8];
+
+fn to_planes(bytes: &[u8; 16]) -> Planes {
+  let mut planes = [0; 8];
+  for (i, plane) in planes.iter_mut().enumerate() {
+    for (j, &byte) in bytes.iter().enumerate() {
+      *plane |= u16::from((byte >> i) & 1) << j;
+    }
+  }
+  planes
+}
+
+fn from_planes(planes: &Planes) -> [u8; 16] {
+  let mut bytes = [0; 16];
+  for (j, byte) in bytes.iter_mut().enumerate() {
+    for (i, plane) in planes.iter().enumerate() {
+      *byte |= (((plane >> j) & 1) as u8) << i;
+    }
+  }
+  bytes
+}
+
+fn xor_planes(a: &Planes, b: &Planes) -> Planes {
+  let mut result = *a;
+  for (r, b) in result.iter_mut().zip(b.iter()) {
+    *r ^= b;
+  }
+  result
+}
+
+// Moves byte table[j] of every plane to position j.
+fn permute_planes(planes: &Planes, table: &[usize; 16]) -> Planes {
+  let mut result = [0; 8];
+  for (r, plane) in result.iter_mut().zip(planes.iter()) {
+    for (j, &source) in table.iter().enumerate() {
+      *r |= ((plane >> source) & 1) << j;
+    }
+  }
+  result
+}
+
+// Multiplication in GF(2^8) of all sixteen bytes at once.
+fn gmul_planes(a: &Planes, b: &Planes) -> Planes {
+  let mut product = [0u16; 15];
+  for (i, a) in a.iter().enumerate() {
+    for (j, b) in b.iter().enumerate() {
+      product[i + j] ^= a & b;
+    }
+  }
+  // x^8 = x^4 + x^3 + x + 1
+  for k in (8..15).rev() {
+    product[k - 8] ^= product[k];
+    product[k - 7] ^= product[k];
+    product[k - 5] ^= product[k];
+    product[k - 4] ^= product[k];
+  }
+  let mut result = [0; 8];
+  result.copy_from_slice(&product[..8]);
+  result
+}
+
+// Multiplication by x.
+fn xtime_planes(a: &Planes) -> Planes {
+  [
+    a[7],
+    a[0] ^ a[7],
+    a[1],
+    a[2] ^ a[7],
+    a[3] ^ a[7],
+    a[4],
+    a[5],
+    a[6],
+  ]
+}
+
+fn mul_constant_planes(a: &Planes, mut constant: u8) -> Planes {
+  let mut result = [0; 8];
+  let mut power = *a;
+  while constant != 0 {
+    if constant & 1 == 1 {
+      result = xor_planes(&result, &power);
+    }
+    power = xtime_planes(&power);
+    constant >>= 1;
+  }
+  result
+}
+
+// x^254, which is the inverse of every byte other than 0, and maps 0 to 0.
+fn inverse_planes(a: &Planes) -> Planes {
+  let mut result = *a;
+  for _ in 0..6 {
+    result = gmul_planes(&gmul_planes(&result, &result), a);
+  }
+  gmul_planes(&result, &result)
+}
+
+fn sbox_planes(a: &Planes) -> Planes {
+  let b = inverse_planes(a);
+  let mut result = [0; 8];
+  for (i, r) in result.iter_mut().enumerate() {
+    let constant = if (0x63 >> i) & 1 == 1 { 0xffff } else { 0 };
+    *r = b[i] ^ b[(i + 4) % 8] ^ b[(i + 5) % 8] ^ b[(i + 6) % 8] ^ b[(i + 7) % 8] ^ constant;
+  }
+  result
+}
+
+fn inv_sbox_planes(a: &Planes) -> Planes {
+  let mut b = [0; 8];
+  for (i, r) in b.iter_mut().enumerate() {
+    let constant = if (0x05 >> i) & 1 == 1 { 0xffff } else { 0 };
+    *r = a[(i + 2) % 8] ^ a[(i + 5) % 8] ^ a[(i + 7) % 8] ^ constant;
+  }
+  inverse_planes(&b)
+}
+
+// Row r of each column replaced with row r + shift.
+const fn column_rotation(shift: usize) -> [usize; 16] {
+  let mut table = [0; 16];
+  let mut j = 0;
+  while j < 16 {
+    table[j] = j - j % 4 + (j + shift) % 4;
+    j += 1;
+  }
+  table
+}
+
+const COLUMN_ROTATIONS: [[usize; 16]; 4] = [
+  column_rotation(0),
+  column_rotation(1),
+  column_rotation(2),
+  column_rotation(3),
+];
+
+// The first row of the matrix, which is all a circulant matrix needs.
+fn mix_columns_planes(a: &Planes, matrix: &[u8; 16]) -> Planes {
+  let mut result = [0; 8];
+  for (rotation, &coefficient) in COLUMN_ROTATIONS.iter().zip(matrix.iter()) {
+    let rotated = permute_planes(a, rotation);
+    result = xor_planes(&result, &mul_constant_planes(&rotated, coefficient));
+  }
+  result
+}
+
+fn bitsliced_encrypt_block(block: &[u8; 16], round_keys: &[[u8; 16]]) -> [u8; 16] {
+  let last_round = round_keys.len() - 1;
+  let mut state = to_planes(block);
+  for (round, round_key) in round_keys.iter().enumerate() {
+    if round != 0 {
+      state = permute_planes(&sbox_planes(&state), &ROW_SHIFTS);
+      if round != last_round {
+        state = mix_columns_planes(&state, &COLUMN_MATRIX);
+      }
+    }
+    state = xor_planes(&state, &to_planes(round_key));
+  }
+  from_planes(&state)
+}
+
+fn bitsliced_decrypt_block(block: &[u8; 16], round_keys: &[[u8; 16]]) -> [u8; 16] {
+  let last_round = round_keys.len() - 1;
+  let mut state = to_planes(block);
+  for (round, round_key) in round_keys.iter().enumerate() {
+    if round != 0 {
+      state = permute_planes(&inv_sbox_planes(&state), &INV_ROW_SHIFTS);
+      if round != last_round {
+        state = mix_columns_planes(&state, &INV_COLUMN_MATRIX);
+      }
+    }
+    state = xor_planes(&state, &to_planes(round_key));
+  }
+  from_planes(&state)
+}
+
+// Interchangeable implementations of the block function, which all produce
+// the same results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+  // Each step straight from the specification
+  Reference,
+  // SubBytes, ShiftRows and MixColumns folded into table lookups
+  TTable,
+  // Constant time, with no lookups indexed by secret data
+  Bitsliced,
+}
+
+// Messages at least this long are split across threads in the modes where
+// blocks are independent of each other.
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 1 << 14;
+
+// A key expanded once into the round keys for both directions, so that it can
+// be reused for any number of blocks.
+#[derive(Clone)]
+pub struct Cipher {
+  backend: Backend,
+  parallel_threshold: usize,
+  encryption_keys: Vec<[u8; 16]>,
+  // Round keys for the equivalent inverse cipher: in reverse order, with
+  // InvMixColumns applied to all but the first and the last.
+  decryption_keys: Vec<[u8; 16]>,
+}
+
+impl Cipher {
+  pub fn new(key: &[u8]) -> Cipher {
+    Cipher::with_backend(key, Backend::TTable)
+  }
+
+  pub fn with_backend(key: &[u8], backend: Backend) -> Cipher {
+    let encryption_keys: Vec<[u8; 16]> = expand_key(key)
+      .chunks(16)
+      .map(util::convert_to_fixed_array)
+      .collect();
+    Cipher::from_round_keys(encryption_keys, backend)
+  }
+
+  // AES cut short after the given number of rounds, the last of which skips
+  // MixColumns just like the final round of the full cipher.
+  pub fn reduced(key: &[u8], rounds: usize, backend: Backend) -> Cipher {
+    let mut encryption_keys: Vec<[u8; 16]> = expand_key(key)
+      .chunks(16)
+      .map(util::convert_to_fixed_array)
+      .collect();
+    assert!(
+      rounds >= 1 && rounds < encryption_keys.len(),
+      "Unsupported number of rounds {}",
+      rounds
+    );
+    encryption_keys.truncate(rounds + 1);
+    Cipher::from_round_keys(encryption_keys, backend)
+  }
+
+  fn from_round_keys(encryption_keys: Vec<[u8; 16]>, backend: Backend) -> Cipher {
+    let last_round = encryption_keys.len() - 1;
+    let decryption_keys = encryption_keys
+      .iter()
+      .rev()
+      .enumerate()
+      .map(|(round, &round_key)| {
+        let mut round_key = round_key;
+        if round != 0 && round != last_round {
+          inv_mix_columns(&mut round_key);
+        }
+        round_key
+      })
+      .collect();
+    Cipher {
+      backend,
+      parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+      encryption_keys,
+      decryption_keys,
+    }
+  }
+
+  pub fn backend(&self) -> Backend {
+    self.backend
+  }
+
+  // The message length in bytes from which ECB, CBC decryption and CTR
+  // process blocks in parallel.
+  pub fn set_parallel_threshold(&mut self, threshold: usize) {
+    self.parallel_threshold = threshold;
+  }
+
+  pub fn rounds(&self) -> usize {
+    self.encryption_keys.len() - 1
+  }
+
+  pub fn encrypt_block(&self, block: &[u8; 16]) -> [u8; 16] {
+    match self.backend {
+      Backend::Reference => self.reference_encrypt_block(block, None),
+      Backend::TTable => t_table_rounds(block, &self.encryption_keys, &TE, &SBOX, 1),
+      Backend::Bitsliced => bitsliced_encrypt_block(block, &self.encryption_keys),
+    }
+  }
+
+  pub fn decrypt_block(&self, block: &[u8; 16]) -> [u8; 16] {
+    match self.backend {
+      Backend::Reference => self.reference_decrypt_block(block),
+      Backend::TTable => t_table_rounds(block, &self.decryption_keys, &TD, &INV_SBOX, 3),
+      Backend::Bitsliced => bitsliced_decrypt_block(block, &self.decryption_keys),
+    }
+  }
+
+  // Encrypts as the reference implementation would, except that the
+  // difference is xored into one byte of the state just before MixColumns of
+  // the given round, as a glitch on a device might.
+  pub fn encrypt_block_with_fault(
+    &self,
+    block: &[u8; 16],
+    round: usize,
+    index: usize,
+    difference: u8,
+  ) -> [u8; 16] {
+    assert!(
+      round >= 1 && round < self.rounds(),
+      "Round {} has no MixColumns",
+      round
+    );
+    self.reference_encrypt_block(block, Some((round, index, difference)))
+  }
+
+  fn reference_encrypt_block(
+    &self,
+    block: &[u8; 16],
+    fault: Option<(usize, usize, u8)>,
+  ) -> [u8; 16] {
+    let last_round = self.rounds();
+    let mut state = *block;
+    for (round, round_key) in self.encryption_keys.iter().enumerate() {
+      if round != 0 {
+        sbox(&mut state);
+        shift_rows(&mut state);
+        if let Some((fault_round, index, difference)) = fault {
+          if fault_round == round {
+            state[index] ^= difference;
           }
         }
+        if round != last_round {
+          mix_columns(&mut state);
+        }
       }
+      add_round_key(&mut state, round_key);
     }
-    Operation::Decrypt => {
-      for (round, round_key) in expanded_key.chunks(STATE_SIZE).rev().enumerate() {
-        match round {
-          0 => {
-            add_round_key(&mut state, round_key);
-          }
-          n if n != last_round => {
-            inv_shift_rows(&mut state);
-            inv_sbox(&mut state);
-            add_round_key(&mut state, round_key);
-            inv_mix_columns(&mut state);
-          }
-          _ => {
-            inv_shift_rows(&mut state);
-            inv_sbox(&mut state);
-            add_round_key(&mut state, round_key);
-          }
+    state
+  }
+
+  fn reference_decrypt_block(&self, block: &[u8; 16]) -> [u8; 16] {
+    let last_round = self.rounds();
+    let mut state = *block;
+    for (round, round_key) in self.decryption_keys.iter().enumerate() {
+      if round != 0 {
+        inv_sbox(&mut state);
+        inv_shift_rows(&mut state);
+        if round != last_round {
+          inv_mix_columns(&mut state);
         }
       }
+      add_round_key(&mut state, round_key);
     }
-  };
-  state
+    state
+  }
+
+  pub fn perform(&self, data: &[u8], operation: Operation, cipher_mode: CipherMode) -> Vec<u8> {
+    let block_operation = block_operation(operation, &cipher_mode);
+    let mut cipher_mode_impl = self.cipher_mode_impl(operation, cipher_mode);
+    cipher_mode_impl.transform_chunks(data, &|block| {
+      modes::transform(self, block, block_operation)
+    })
+  }
+
+  fn cipher_mode_impl(
+    &self,
+    operation: Operation,
+    cipher_mode: CipherMode,
+  ) -> Box<dyn CipherModeImpl> {
+    let parallel_threshold = self.parallel_threshold;
+    if let CipherMode::GCM(nonce, additional_data) = cipher_mode {
+      return Box::new(GCMCipherMode {
+        nonce,
+        additional_data,
+        operation,
+        parallel_threshold,
+      });
+    }
+    let mode = generic_mode(&cipher_mode).unwrap();
+    modes::cipher_mode_impl(mode, 16, operation, parallel_threshold)
+  }
+
+  // GCM decryption that reports a forged or corrupted ciphertext instead of
+  // panicking.
+  pub fn gcm_open(
+    &self,
+    nonce: [u8; 12],
+    additional_data: &[u8],
+    data: &[u8],
+  ) -> Result<Vec<u8>, IntegrityError> {
+    let mut mode = GCMCipherMode {
+      nonce,
+      additional_data: additional_data.to_vec(),
+      operation: Operation::Decrypt,
+      parallel_threshold: self.parallel_threshold,
+    };
+    mode.seal_or_open(data, &|block| {
+      modes::transform(self, block, Operation::Encrypt)
+    })
+  }
+}
+
+impl BlockCipher for Cipher {
+  const BLOCK_SIZE: usize = 16;
+
+  fn encrypt(&self, block: &mut [u8]) {
+    let ciphertext = self.encrypt_block(&util::convert_to_fixed_array(block));
+    block.copy_from_slice(&ciphertext);
+  }
+
+  fn decrypt(&self, block: &mut [u8]) {
+    let plaintext = self.decrypt_block(&util::convert_to_fixed_array(block));
+    block.copy_from_slice(&plaintext);
+  }
+
+  fn parallel_threshold(&self) -> usize {
+    self.parallel_threshold
+  }
+}
+
+// The same mode for any block cipher, which is all but GCM.
+fn generic_mode(cipher_mode: &CipherMode) -> Option<modes::Mode> {
+  Some(match *cipher_mode {
+    CipherMode::ECB => modes::Mode::ECB,
+    CipherMode::CBC(iv) => modes::Mode::CBC(iv.to_vec()),
+    CipherMode::CTR(counter) => modes::Mode::CTR(counter.to_vec()),
+    CipherMode::CFB(iv) => modes::Mode::CFB(iv.to_vec()),
+    CipherMode::CFB8(iv) => modes::Mode::CFB8(iv.to_vec()),
+    CipherMode::OFB(iv) => modes::Mode::OFB(iv.to_vec()),
+    CipherMode::PCBC(iv) => modes::Mode::PCBC(iv.to_vec()),
+    CipherMode::CBCCS1(iv) => modes::Mode::CBCCS1(iv.to_vec()),
+    CipherMode::CBCCS2(iv) => modes::Mode::CBCCS2(iv.to_vec()),
+    CipherMode::CBCCS3(iv) => modes::Mode::CBCCS3(iv.to_vec()),
+    CipherMode::GCM(_, _) => return None,
+  })
+}
+
+fn block_operation(operation: Operation, cipher_mode: &CipherMode) -> Operation {
+  match generic_mode(cipher_mode) {
+    Some(mode) => modes::block_operation(operation, &mode),
+    None => Operation::Encrypt,
+  }
+}
+
+#[test]
+fn fips197_vectors() {
+  use util::parse_byte_string;
+  let plaintext =
+    util::convert_to_fixed_array(&parse_byte_string("00112233445566778899aabbccddeeff"));
+  let vectors = [
+    (
+      "000102030405060708090a0b0c0d0e0f",
+      "69c4e0d86a7b0430d8cdb78070b4c55a",
+    ),
+    (
+      "000102030405060708090a0b0c0d0e0f1011121314151617",
+      "dda97ca4864cdfe06eaf70a0ec0d7191",
+    ),
+    (
+      "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+      "8ea2b7ca516745bfeafc49904b496089",
+    ),
+  ];
+  for &backend in [Backend::Reference, Backend::TTable, Backend::Bitsliced].iter() {
+    for &(key, ciphertext) in vectors.iter() {
+      let cipher = Cipher::with_backend(&parse_byte_string(key), backend);
+      let ciphertext = util::convert_to_fixed_array(&parse_byte_string(ciphertext));
+      assert_eq!(ciphertext, cipher.encrypt_block(&plaintext));
+      assert_eq!(plaintext, cipher.decrypt_block(&ciphertext));
+    }
+  }
+}
+
+#[test]
+fn reduced_backends_agree() {
+  let key: Vec<u8> = (0..16).collect();
+  let plaintext = *b"YELLOW SUBMARINE";
+  for rounds in 1..10 {
+    let reference = Cipher::reduced(&key, rounds, Backend::Reference);
+    let ciphertext = reference.encrypt_block(&plaintext);
+    assert_eq!(plaintext, reference.decrypt_block(&ciphertext));
+    for &backend in [Backend::TTable, Backend::Bitsliced].iter() {
+      let cipher = Cipher::reduced(&key, rounds, backend);
+      assert_eq!(ciphertext, cipher.encrypt_block(&plaintext));
+      assert_eq!(plaintext, cipher.decrypt_block(&ciphertext));
+    }
+  }
+}
+
+#[cfg(test)]
+mod qctests {
+  use super::{Backend, Cipher, CipherMode, Operation};
+  use util;
+
+  quickcheck! {
+    fn backends_match_reference(key: Vec<u8>, key_size: u8, block: Vec<u8>) -> bool {
+      let mut key = key;
+      key.resize([16, 24, 32][key_size as usize % 3], 0);
+      let mut block = block;
+      block.resize(16, 0);
+      let block = util::convert_to_fixed_array(&block);
+      let reference = Cipher::with_backend(&key, Backend::Reference);
+      let ciphertext = reference.encrypt_block(&block);
+      [Backend::TTable, Backend::Bitsliced].iter().all(|&backend| {
+        let cipher = Cipher::with_backend(&key, backend);
+        ciphertext == cipher.encrypt_block(&block)
+          && reference.decrypt_block(&ciphertext) == cipher.decrypt_block(&ciphertext)
+      })
+    }
+
+    fn parallel_matches_serial(key: Vec<u8>, iv: Vec<u8>, data: Vec<u8>) -> bool {
+      let mut key = key;
+      key.resize(16, 0);
+      let mut iv = iv;
+      iv.resize(16, 0);
+      let iv = util::convert_to_fixed_array(&iv);
+      let mut serial = Cipher::new(&key);
+      serial.set_parallel_threshold(usize::MAX);
+      let mut parallel = Cipher::new(&key);
+      parallel.set_parallel_threshold(0);
+      let blocks = &data[..data.len() / 16 * 16];
+      let same = |data: &[u8], operation: Operation, cipher_mode: CipherMode| {
+        serial.perform(data, operation, cipher_mode.clone())
+          == parallel.perform(data, operation, cipher_mode)
+      };
+      same(blocks, Operation::Encrypt, CipherMode::ECB)
+        && same(blocks, Operation::Decrypt, CipherMode::ECB)
+        && same(blocks, Operation::Decrypt, CipherMode::CBC(iv))
+        && same(&data, Operation::Encrypt, CipherMode::CTR(iv))
+        && same(&data, Operation::Encrypt, CipherMode::GCM([0; 12], vec![]))
+    }
+
+    fn streaming_matches_perform(key: Vec<u8>, iv: Vec<u8>, data: Vec<u8>, chunk_size: u8) -> bool {
+      use super::{Decryptor, Encryptor};
+      use pkcs7;
+      use std::io::{Read, Write};
+      let mut key = key;
+      key.resize(16, 0);
+      let mut iv = iv;
+      iv.resize(16, 0);
+      let iv = util::convert_to_fixed_array(&iv);
+      let cipher = Cipher::new(&key);
+      let chunk_size = usize::from(chunk_size).max(1);
+      let modes = [
+        CipherMode::ECB,
+        CipherMode::CBC(iv),
+        CipherMode::CTR(iv),
+        CipherMode::CFB(iv),
+        CipherMode::CFB8(iv),
+        CipherMode::OFB(iv),
+        CipherMode::PCBC(iv),
+      ];
+      modes.iter().all(|cipher_mode| {
+        let mut encryptor = Encryptor::new(Vec::new(), cipher.clone(), cipher_mode.clone());
+        for chunk in data.chunks(chunk_size) {
+          encryptor.write_all(chunk).unwrap();
+        }
+        let ciphertext = encryptor.finish().unwrap();
+        let expected = if super::is_padded(cipher_mode) {
+          cipher.perform(&pkcs7::pad(&data, 16), Operation::Encrypt, cipher_mode.clone())
+        } else {
+          cipher.perform(&data, Operation::Encrypt, cipher_mode.clone())
+        };
+
+        let mut decryptor = Decryptor::new(&ciphertext[..], cipher.clone(), cipher_mode.clone());
+        let mut decrypted = Vec::new();
+        let mut buf = vec![0; chunk_size];
+        loop {
+          let read = decryptor.read(&mut buf).unwrap();
+          if read == 0 {
+            break;
+          }
+          decrypted.extend_from_slice(&buf[..read]);
+        }
+        ciphertext == expected && decrypted == data
+      })
+    }
+  }
 }
 
 #[derive(Clone, Copy)]
@@ -399,86 +1103,297 @@ pub enum Operation {
   Decrypt,
 }
 
-trait CipherModeImpl {
-  fn transform_chunks(
-    &mut self,
-    data: &[u8],
-    transform: &(Fn(&[u8; 16]) -> [u8; 16] + Sync),
-  ) -> Vec<u8>;
+struct GCMCipherMode {
+  nonce: [u8; 12],
+  additional_data: Vec<u8>,
+  operation: Operation,
+  parallel_threshold: usize,
 }
 
-struct ECBCipherMode {}
+pub const GCM_TAG_SIZE: usize = 16;
 
-struct CBCCipherMode {
-  initialization_vector: [u8; 16],
-  operation: Operation,
+// GHASH over the zero padded additional data and ciphertext followed by a
+// block holding both of their lengths in bits.
+pub fn ghash(h: gf128::Element, additional_data: &[u8], ciphertext: &[u8]) -> gf128::Element {
+  ghash_blocks(additional_data, ciphertext)
+    .iter()
+    .fold(gf128::Element::zero(), |accum, block| {
+      (accum + gf128::Element::from_bytes(block)) * h
+    })
 }
 
-impl CBCCipherMode {
-  fn transform(&mut self, chunk: &[u8; 16], transform: &Fn(&[u8; 16]) -> [u8; 16]) -> [u8; 16] {
+// The blocks GHASH operates on, so that the tag can be viewed as a polynomial
+// in H: the first block is the coefficient of the highest power of H.
+pub fn ghash_blocks(additional_data: &[u8], ciphertext: &[u8]) -> Vec<[u8; 16]> {
+  let mut blocks = Vec::new();
+  for data in [additional_data, ciphertext].iter() {
+    for chunk in data.chunks(16) {
+      let mut block = [0; 16];
+      block[..chunk.len()].copy_from_slice(chunk);
+      blocks.push(block);
+    }
+  }
+  let mut lengths = [0; 16];
+  lengths[..8].copy_from_slice(&u64_to_be_bytes(additional_data.len() as u64 * 8));
+  lengths[8..].copy_from_slice(&u64_to_be_bytes(ciphertext.len() as u64 * 8));
+  blocks.push(lengths);
+  blocks
+}
+
+fn u64_to_be_bytes(n: u64) -> [u8; 8] {
+  let mut bytes = [0; 8];
+  for (i, byte) in bytes.iter_mut().enumerate() {
+    *byte = (n >> (8 * (7 - i))) as u8;
+  }
+  bytes
+}
+
+impl GCMCipherMode {
+  fn tag(
+    &self,
+    ciphertext: &[u8],
+    initial_counter: &[u8; 16],
+    transform: &(dyn Fn(&mut [u8]) + Sync),
+  ) -> [u8; 16] {
+    let mut h = [0; 16];
+    transform(&mut h);
+    let mut s = *initial_counter;
+    transform(&mut s);
+    let h = gf128::Element::from_bytes(&h);
+    (ghash(h, &self.additional_data, ciphertext) + gf128::Element::from_bytes(&s)).to_bytes()
+  }
+}
+
+impl GCMCipherMode {
+  fn seal_or_open(
+    &mut self,
+    data: &[u8],
+    transform: &(dyn Fn(&mut [u8]) + Sync),
+  ) -> Result<Vec<u8>, IntegrityError> {
+    let mut initial_counter = [0; 16];
+    initial_counter[..12].copy_from_slice(&self.nonce);
+    initial_counter[15] = 1;
+    // The message itself is encrypted from the counter block after the
+    // initial one.
+    let mut first_counter = initial_counter.to_vec();
+    first_counter[15] = 2;
+    let mut ctr = CTRCipherMode::new(first_counter, 4, self.parallel_threshold);
     match self.operation {
       Operation::Encrypt => {
-        xor::buffer_mut(&mut self.initialization_vector, xor::Key::FullBuffer(chunk));
-        self.initialization_vector = transform(&self.initialization_vector);
-        util::convert_to_fixed_array(&self.initialization_vector)
+        let mut v = ctr.transform_chunks(data, transform);
+        let tag = self.tag(&v, &initial_counter, transform);
+        v.extend_from_slice(&tag);
+        Ok(v)
       }
       Operation::Decrypt => {
-        let mut plaintext = transform(chunk);
-        xor::buffer_mut(
-          &mut plaintext,
-          xor::Key::FullBuffer(&self.initialization_vector),
-        );
-        self.initialization_vector = util::convert_to_fixed_array(chunk);
-        plaintext
+        if data.len() < GCM_TAG_SIZE {
+          return Err(IntegrityError::InvalidLength(data.len()));
+        }
+        let (ciphertext, tag) = data.split_at(data.len() - GCM_TAG_SIZE);
+        if !aead::tags_match(tag, &self.tag(ciphertext, &initial_counter, transform)) {
+          return Err(IntegrityError::Mismatch);
+        }
+        Ok(ctr.transform_chunks(ciphertext, transform))
       }
     }
   }
 }
 
-impl CipherModeImpl for ECBCipherMode {
-  fn transform_chunks(
-    &mut self,
-    data: &[u8],
-    transform: &(Fn(&[u8; 16]) -> [u8; 16] + Sync),
-  ) -> Vec<u8> {
-    let mut v = Vec::with_capacity(data.len());
-    for chunk in data.chunks(16) {
-      v.extend(&transform(&util::convert_to_fixed_array(chunk)));
+impl CipherModeImpl for GCMCipherMode {
+  // perform has no way to report a bad tag, so it panics; gcm_open doesn't.
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    self
+      .seal_or_open(data, transform)
+      .unwrap_or_else(|error| panic!("GCM decryption failed: {}", error))
+  }
+}
+
+// Why the deterministic modes rejected a ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegrityError {
+  // No ciphertext of this length can come out of the mode.
+  InvalidLength(usize),
+  // The ciphertext was modified, or produced under another key or other
+  // associated data.
+  Mismatch,
+}
+
+impl fmt::Display for IntegrityError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      IntegrityError::InvalidLength(length) => write!(f, "Invalid ciphertext length {}", length),
+      IntegrityError::Mismatch => write!(f, "Integrity check failed"),
     }
-    v
   }
 }
 
-impl CipherModeImpl for CBCCipherMode {
-  fn transform_chunks(
-    &mut self,
-    data: &[u8],
-    transform: &(Fn(&[u8; 16]) -> [u8; 16] + Sync),
-  ) -> Vec<u8> {
-    let mut v = Vec::with_capacity(data.len());
-    for chunk in data.chunks(16) {
-      v.extend(
-        self
-          .transform(&util::convert_to_fixed_array(chunk), transform)
-          .iter(),
-      );
+impl error::Error for IntegrityError {}
+
+const KEY_WRAP_IV: u64 = 0xa6a6_a6a6_a6a6_a6a6;
+
+fn u64_from_be_bytes(bytes: &[u8]) -> u64 {
+  bytes
+    .iter()
+    .fold(0, |accum, &byte| (accum << 8) | u64::from(byte))
+}
+
+// RFC 3394 key wrap: six passes of a Feistel-like network over the 8 byte
+// halves of the key, with an integrity check value that only survives
+// unwrapping under the right key-encryption key.
+pub fn wrap_key(kek: &[u8], key: &[u8]) -> Vec<u8> {
+  assert!(
+    key.len() >= 16 && key.len().is_multiple_of(8),
+    "Can't wrap a key of {} bytes",
+    key.len()
+  );
+  let cipher = Cipher::new(kek);
+  let mut a = KEY_WRAP_IV;
+  let mut r: Vec<[u8; 8]> = key
+    .chunks(8)
+    .map(|chunk| {
+      let mut half = [0; 8];
+      half.copy_from_slice(chunk);
+      half
+    })
+    .collect();
+  let n = r.len() as u64;
+  for j in 0..6 {
+    for (i, r_i) in r.iter_mut().enumerate() {
+      let mut block = [0; 16];
+      block[..8].copy_from_slice(&u64_to_be_bytes(a));
+      block[8..].copy_from_slice(r_i);
+      let b = cipher.encrypt_block(&block);
+      a = u64_from_be_bytes(&b[..8]) ^ (n * j + i as u64 + 1);
+      r_i.copy_from_slice(&b[8..]);
     }
-    v
   }
+  let mut wrapped = u64_to_be_bytes(a).to_vec();
+  for half in r.iter() {
+    wrapped.extend_from_slice(half);
+  }
+  wrapped
 }
 
-pub fn perform(data: &[u8], key: &[u8], operation: Operation, cipher_mode: CipherMode) -> Vec<u8> {
-  let expanded_key = expand_key(key);
-  let mut cipher_mode_impl: Box<CipherModeImpl> = match cipher_mode {
-    CipherMode::ECB => Box::new(ECBCipherMode {}),
-    CipherMode::CBC(iv) => Box::new(CBCCipherMode {
-      initialization_vector: iv,
-      operation,
-    }),
+pub fn unwrap_key(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, IntegrityError> {
+  if wrapped.len() < 24 || !wrapped.len().is_multiple_of(8) {
+    return Err(IntegrityError::InvalidLength(wrapped.len()));
+  }
+  let cipher = Cipher::new(kek);
+  let mut a = u64_from_be_bytes(&wrapped[..8]);
+  let mut r: Vec<u8> = wrapped[8..].to_vec();
+  let n = (r.len() / 8) as u64;
+  for j in (0..6).rev() {
+    for (i, r_i) in r.chunks_mut(8).enumerate().rev() {
+      let mut block = [0; 16];
+      block[..8].copy_from_slice(&u64_to_be_bytes(a ^ (n * j + i as u64 + 1)));
+      block[8..].copy_from_slice(r_i);
+      let b = cipher.decrypt_block(&block);
+      a = u64_from_be_bytes(&b[..8]);
+      r_i.copy_from_slice(&b[8..]);
+    }
+  }
+  if a != KEY_WRAP_IV {
+    return Err(IntegrityError::Mismatch);
+  }
+  Ok(r)
+}
+
+// S2V from RFC 5297: a CMAC based PRF over a vector of strings, the last of
+// which is the plaintext.
+fn s2v(cipher: &Cipher, strings: &[&[u8]]) -> [u8; 16] {
+  let (last, rest) = strings.split_last().unwrap();
+  let mut d = cmac::cmac(cipher, &[0; 16]);
+  for string in rest.iter() {
+    d = cmac::dbl(&d);
+    xor::buffer_mut(&mut d, xor::Key::FullBuffer(&cmac::cmac(cipher, string)));
+  }
+  let t = if last.len() >= 16 {
+    let mut t = last.to_vec();
+    let end = t.len() - 16;
+    xor::buffer_mut(&mut t[end..], xor::Key::FullBuffer(&d));
+    t
+  } else {
+    let mut t = cmac::dbl(&d);
+    xor::buffer_mut(&mut t[..last.len()], xor::Key::FullBuffer(last));
+    t[last.len()] ^= 0x80;
+    t.to_vec()
   };
-  cipher_mode_impl.transform_chunks(data, &|pre_transformed_chunk| {
-    transform_chunk(pre_transformed_chunk, &expanded_key, operation)
-  })
+  cmac::cmac(cipher, &t)
+}
+
+// SIV splits its key into a CMAC key and a CTR key of the same size.
+fn siv_ciphers(key: &[u8]) -> (Cipher, Cipher) {
+  assert!(
+    [32, 48, 64].contains(&key.len()),
+    "SIV keys are 32, 48 or 64 bytes, got {}",
+    key.len()
+  );
+  let (mac_key, ctr_key) = key.split_at(key.len() / 2);
+  (Cipher::new(mac_key), Cipher::new(ctr_key))
+}
+
+// The synthetic IV doubles as the initial counter block, with two bits
+// cleared so that implementations can increment its low 64 bits alone.
+fn siv_counter(v: &[u8; 16]) -> [u8; 16] {
+  let mut q = *v;
+  q[8] &= 0x7f;
+  q[12] &= 0x7f;
+  q
+}
+
+// RFC 5297 AES-SIV: deterministic authenticated encryption, where the same
+// plaintext and associated data always give the same ciphertext, prefixed by
+// the 16 byte synthetic IV. A nonce, if any, is just the last piece of
+// associated data.
+pub fn siv_encrypt(key: &[u8], associated_data: &[&[u8]], plaintext: &[u8]) -> Vec<u8> {
+  let (mac_cipher, ctr_cipher) = siv_ciphers(key);
+  let mut strings = associated_data.to_vec();
+  strings.push(plaintext);
+  let v = s2v(&mac_cipher, &strings);
+  let mut ciphertext = v.to_vec();
+  ciphertext.extend(ctr_cipher.perform(
+    plaintext,
+    Operation::Encrypt,
+    CipherMode::CTR(siv_counter(&v)),
+  ));
+  ciphertext
+}
+
+pub fn siv_decrypt(
+  key: &[u8],
+  associated_data: &[&[u8]],
+  ciphertext: &[u8],
+) -> Result<Vec<u8>, IntegrityError> {
+  if ciphertext.len() < 16 {
+    return Err(IntegrityError::InvalidLength(ciphertext.len()));
+  }
+  let (mac_cipher, ctr_cipher) = siv_ciphers(key);
+  let (v, ciphertext) = ciphertext.split_at(16);
+  let v = util::convert_to_fixed_array(v);
+  let plaintext = ctr_cipher.perform(
+    ciphertext,
+    Operation::Decrypt,
+    CipherMode::CTR(siv_counter(&v)),
+  );
+  let mut strings = associated_data.to_vec();
+  strings.push(&plaintext);
+  if !aead::tags_match(&s2v(&mac_cipher, &strings), &v) {
+    return Err(IntegrityError::Mismatch);
+  }
+  Ok(plaintext)
+}
+
+pub fn perform(data: &[u8], key: &[u8], operation: Operation, cipher_mode: CipherMode) -> Vec<u8> {
+  Cipher::new(key).perform(data, operation, cipher_mode)
+}
+
+pub fn gcm_open(
+  key: &[u8],
+  nonce: [u8; 12],
+  additional_data: &[u8],
+  data: &[u8],
+) -> Result<Vec<u8>, IntegrityError> {
+  Cipher::new(key).gcm_open(nonce, additional_data, data)
 }
 
 #[test]
@@ -513,3 +1428,650 @@ fn ecb_once_32() {
     &perform(&ciphertext, &key, Operation::Decrypt, CipherMode::ECB)
   );
 }
+
+#[test]
+fn ctr_sp800_38a() {
+  use util::parse_byte_string;
+  let key = parse_byte_string("2b7e151628aed2a6abf7158809cf4f3c");
+  let counter =
+    util::convert_to_fixed_array(&parse_byte_string("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"));
+  let plaintext = parse_byte_string("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710");
+  let ciphertext = parse_byte_string("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee");
+  assert_eq!(
+    ciphertext,
+    perform(
+      &plaintext,
+      &key,
+      Operation::Encrypt,
+      CipherMode::CTR(counter)
+    )
+  );
+  assert_eq!(
+    plaintext,
+    perform(
+      &ciphertext,
+      &key,
+      Operation::Decrypt,
+      CipherMode::CTR(counter)
+    )
+  );
+  // Partial final blocks just use a prefix of the keystream
+  assert_eq!(
+    &ciphertext[..20],
+    &perform(
+      &plaintext[..20],
+      &key,
+      Operation::Encrypt,
+      CipherMode::CTR(counter)
+    )[..]
+  );
+}
+
+#[test]
+fn feedback_modes_sp800_38a() {
+  use util::parse_byte_string;
+  let key = parse_byte_string("2b7e151628aed2a6abf7158809cf4f3c");
+  let iv = util::convert_to_fixed_array(&parse_byte_string("000102030405060708090a0b0c0d0e0f"));
+  let plaintext = parse_byte_string("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710");
+  let vectors = [
+    (CipherMode::CFB(iv), "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6"),
+    (CipherMode::OFB(iv), "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed8259740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"),
+    // Only the first 18 bytes are published for CFB8.
+    (CipherMode::CFB8(iv), "3b79424c9c0dd436bace9e0ed4586a4f32b9"),
+  ];
+  for &(ref cipher_mode, ciphertext) in vectors.iter() {
+    let ciphertext = parse_byte_string(ciphertext);
+    let plaintext = &plaintext[..ciphertext.len()];
+    assert_eq!(
+      ciphertext,
+      perform(plaintext, &key, Operation::Encrypt, cipher_mode.clone())
+    );
+    assert_eq!(
+      plaintext,
+      &perform(&ciphertext, &key, Operation::Decrypt, cipher_mode.clone())[..]
+    );
+    // Partial final blocks just use a prefix of the keystream
+    assert_eq!(
+      &ciphertext[..20.min(ciphertext.len())],
+      &perform(
+        &plaintext[..20.min(ciphertext.len())],
+        &key,
+        Operation::Encrypt,
+        cipher_mode.clone()
+      )[..]
+    );
+  }
+}
+
+#[test]
+fn pcbc_round_trip() {
+  use rand::{thread_rng, Rng};
+  let mut rng = thread_rng();
+  let key = util::gen_random_bytes(&mut rng, 16);
+  let iv: [u8; 16] = rng.gen();
+  let plaintext = pkcs7::pad(&util::gen_random_bytes(&mut rng, 100), 16);
+  let ciphertext = perform(&plaintext, &key, Operation::Encrypt, CipherMode::PCBC(iv));
+  assert_ne!(
+    ciphertext,
+    perform(&plaintext, &key, Operation::Encrypt, CipherMode::CBC(iv))
+  );
+  // The first block is the same as in CBC.
+  assert_eq!(
+    ciphertext[..16],
+    perform(
+      &plaintext[..16],
+      &key,
+      Operation::Encrypt,
+      CipherMode::CBC(iv)
+    )[..]
+  );
+  assert_eq!(
+    plaintext,
+    perform(&ciphertext, &key, Operation::Decrypt, CipherMode::PCBC(iv))
+  );
+}
+
+#[test]
+fn cbc_cs3_rfc3962_vectors() {
+  use util::parse_byte_string;
+  let key = b"chicken teriyaki";
+  let plaintext = b"I would like the General Gau's Chicken, please, and wonton soup.";
+  let vectors = [
+    (17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+    (31, "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5"),
+    (32, "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584"),
+    (47, "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e39312523a78662d5be7fcbcc98ebf5"),
+    (48, "97687268d6ecccc0c07b25e25ecfe5849dad8bbb96c4cdc03bc103e1a194bbd839312523a78662d5be7fcbcc98ebf5a8"),
+    (64, "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a84807efe836ee89a526730dbc2f7bc8409dad8bbb96c4cdc03bc103e1a194bbd8"),
+  ];
+  for &(length, ciphertext) in vectors.iter() {
+    let ciphertext = parse_byte_string(ciphertext);
+    let plaintext = &plaintext[..length];
+    let mode = CipherMode::CBCCS3([0; 16]);
+    assert_eq!(
+      ciphertext,
+      perform(plaintext, key, Operation::Encrypt, mode.clone())
+    );
+    assert_eq!(
+      plaintext,
+      &perform(&ciphertext, key, Operation::Decrypt, mode)[..]
+    );
+  }
+}
+
+#[test]
+fn cbc_cs_orders() {
+  let key = [0x42; 16];
+  let iv = [7; 16];
+  let plaintext: Vec<u8> = (0..40).collect();
+  let cs1 = perform(&plaintext, &key, Operation::Encrypt, CipherMode::CBCCS1(iv));
+  let cs2 = perform(&plaintext, &key, Operation::Encrypt, CipherMode::CBCCS2(iv));
+  let cs3 = perform(&plaintext, &key, Operation::Encrypt, CipherMode::CBCCS3(iv));
+  // CS1 keeps the stolen partial block in place, the others move it last.
+  assert_eq!(40, cs1.len());
+  assert_eq!(cs2, cs3);
+  assert_eq!(cs1[..16], cs2[..16]);
+  assert_eq!(cs1[16..24], cs2[32..]);
+  assert_eq!(cs1[24..], cs2[16..32]);
+  // With whole blocks, CS1 and CS2 are plain CBC.
+  let blocks = &plaintext[..32];
+  let cbc = perform(blocks, &key, Operation::Encrypt, CipherMode::CBC(iv));
+  assert_eq!(
+    cbc,
+    perform(blocks, &key, Operation::Encrypt, CipherMode::CBCCS1(iv))
+  );
+  assert_eq!(
+    cbc,
+    perform(blocks, &key, Operation::Encrypt, CipherMode::CBCCS2(iv))
+  );
+  let pairs = [
+    (CipherMode::CBCCS1(iv), cs1),
+    (CipherMode::CBCCS2(iv), cs2),
+    (CipherMode::CBCCS3(iv), cs3),
+  ];
+  for (mode, ciphertext) in pairs.iter() {
+    assert_eq!(
+      plaintext,
+      perform(ciphertext, &key, Operation::Decrypt, mode.clone())
+    );
+  }
+}
+
+#[test]
+fn key_wrap_rfc3394_vectors() {
+  use util::parse_byte_string;
+  let vectors = [
+    (
+      "000102030405060708090a0b0c0d0e0f",
+      "00112233445566778899aabbccddeeff",
+      "1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5",
+    ),
+    (
+      "000102030405060708090a0b0c0d0e0f1011121314151617",
+      "00112233445566778899aabbccddeeff0001020304050607",
+      "031d33264e15d33268f24ec260743edce1c6c7ddee725a936ba814915c6762d2",
+    ),
+    (
+      "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+      "00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f",
+      "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21",
+    ),
+  ];
+  for &(kek, key, wrapped) in vectors.iter() {
+    let kek = parse_byte_string(kek);
+    let key = parse_byte_string(key);
+    let wrapped = parse_byte_string(wrapped);
+    assert_eq!(wrapped, wrap_key(&kek, &key));
+    assert_eq!(Ok(key), unwrap_key(&kek, &wrapped));
+    let mut tampered = wrapped.clone();
+    tampered[10] ^= 1;
+    assert_eq!(Err(IntegrityError::Mismatch), unwrap_key(&kek, &tampered));
+    assert_eq!(
+      Err(IntegrityError::InvalidLength(20)),
+      unwrap_key(&kek, &wrapped[..20])
+    );
+  }
+}
+
+#[test]
+fn siv_rfc5297_vectors() {
+  use util::parse_byte_string;
+  // Deterministic authenticated encryption
+  let key = parse_byte_string("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+  let associated_data = parse_byte_string("101112131415161718191a1b1c1d1e1f2021222324252627");
+  let plaintext = parse_byte_string("112233445566778899aabbccddee");
+  let ciphertext =
+    parse_byte_string("85632d07c6e8f37f950acd320a2ecc9340c02b9690c4dc04daef7f6afe5c");
+  assert_eq!(
+    ciphertext,
+    siv_encrypt(&key, &[&associated_data], &plaintext)
+  );
+  assert_eq!(
+    Ok(plaintext),
+    siv_decrypt(&key, &[&associated_data], &ciphertext)
+  );
+
+  // Nonce based authenticated encryption, with the nonce as the last piece
+  // of associated data
+  let key = parse_byte_string("7f7e7d7c7b7a79787776757473727170404142434445464748494a4b4c4d4e4f");
+  let associated_data = [
+    parse_byte_string(
+      "00112233445566778899aabbccddeeffdeaddadadeaddadaffeeddccbbaa99887766554433221100",
+    ),
+    parse_byte_string("102030405060708090a0"),
+    parse_byte_string("09f911029d74e35bd84156c5635688c0"),
+  ];
+  let associated_data: Vec<&[u8]> = associated_data.iter().map(|ad| &ad[..]).collect();
+  let plaintext = b"this is some plaintext to encrypt using SIV-AES".to_vec();
+  let ciphertext = parse_byte_string("7bdb6e3b432667eb06f4d14bff2fbd0fcb900f2fddbe404326601965c889bf17dba77ceb094fa663b7a3f748ba8af829ea64ad544a272e9c485b62a3fd5c0d");
+  assert_eq!(ciphertext, siv_encrypt(&key, &associated_data, &plaintext));
+  assert_eq!(
+    Ok(plaintext),
+    siv_decrypt(&key, &associated_data, &ciphertext)
+  );
+
+  let mut tampered = ciphertext.clone();
+  tampered[20] ^= 1;
+  assert_eq!(
+    Err(IntegrityError::Mismatch),
+    siv_decrypt(&key, &associated_data, &tampered)
+  );
+  assert_eq!(
+    Err(IntegrityError::Mismatch),
+    siv_decrypt(&key, &associated_data[1..], &ciphertext)
+  );
+  assert_eq!(
+    Err(IntegrityError::InvalidLength(15)),
+    siv_decrypt(&key, &associated_data, &ciphertext[..15])
+  );
+}
+
+#[test]
+fn gcm_test_vectors() {
+  use util::parse_byte_string;
+  let gcm = |nonce: &str, additional_data: &str| {
+    CipherMode::GCM(
+      {
+        let mut n = [0; 12];
+        n.copy_from_slice(&parse_byte_string(nonce));
+        n
+      },
+      parse_byte_string(additional_data),
+    )
+  };
+  // Test cases 1, 2 and 4 from the GCM specification
+  let key = vec![0; 16];
+  assert_eq!(
+    parse_byte_string("58e2fccefa7e3061367f1d57a4e7455a"),
+    perform(
+      &[],
+      &key,
+      Operation::Encrypt,
+      gcm("000000000000000000000000", "")
+    )
+  );
+  assert_eq!(
+    parse_byte_string("0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf"),
+    perform(
+      &[0; 16],
+      &key,
+      Operation::Encrypt,
+      gcm("000000000000000000000000", "")
+    )
+  );
+
+  let key = parse_byte_string("feffe9928665731c6d6a8f9467308308");
+  let plaintext = parse_byte_string("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39");
+  let ciphertext = parse_byte_string("42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e0915bc94fbc3221a5db94fae95ae7121a47");
+  let mode = gcm(
+    "cafebabefacedbaddecaf888",
+    "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+  );
+  assert_eq!(
+    ciphertext,
+    perform(&plaintext, &key, Operation::Encrypt, mode.clone())
+  );
+  assert_eq!(
+    plaintext,
+    perform(&ciphertext, &key, Operation::Decrypt, mode.clone())
+  );
+}
+
+#[test]
+fn gcm_rejects_modified_ciphertext() {
+  use std::panic;
+  let key = vec![0; 16];
+  let mode = CipherMode::GCM([0; 12], b"header".to_vec());
+  let mut ciphertext = perform(b"attack at dawn", &key, Operation::Encrypt, mode.clone());
+  assert_eq!(
+    Ok(b"attack at dawn".to_vec()),
+    gcm_open(&key, [0; 12], b"header", &ciphertext)
+  );
+  assert_eq!(
+    Err(IntegrityError::Mismatch),
+    gcm_open(&key, [0; 12], b"footer", &ciphertext)
+  );
+  assert_eq!(
+    Err(IntegrityError::InvalidLength(15)),
+    gcm_open(&key, [0; 12], b"header", &ciphertext[..15])
+  );
+  ciphertext[0] ^= 1;
+  assert_eq!(
+    Err(IntegrityError::Mismatch),
+    gcm_open(&key, [0; 12], b"header", &ciphertext)
+  );
+  assert!(
+    panic::catch_unwind(|| perform(&ciphertext, &key, Operation::Decrypt, mode.clone())).is_err()
+  );
+}
+
+// GCM and ciphertext stealing need to see the whole message at once.
+fn is_streamable(cipher_mode: &CipherMode) -> bool {
+  !matches!(
+    *cipher_mode,
+    CipherMode::GCM(_, _) | CipherMode::CBCCS1(_) | CipherMode::CBCCS2(_) | CipherMode::CBCCS3(_)
+  )
+}
+
+pub fn is_padded(cipher_mode: &CipherMode) -> bool {
+  generic_mode(cipher_mode).is_some_and(|mode| modes::is_padded(&mode))
+}
+
+// Encrypts everything written to it, passing each whole block on to the inner
+// writer as soon as it is available. The final block, padded if the mode
+// needs it, is only written by finish.
+pub struct Encryptor<W: Write> {
+  inner: W,
+  cipher: Cipher,
+  cipher_mode_impl: Box<dyn CipherModeImpl>,
+  block_operation: Operation,
+  is_padded: bool,
+  buffer: Vec<u8>,
+  // Ciphertext the inner writer hasn't taken yet.
+  pending: Vec<u8>,
+}
+
+impl<W: Write> Encryptor<W> {
+  pub fn new(inner: W, cipher: Cipher, cipher_mode: CipherMode) -> Encryptor<W> {
+    assert!(is_streamable(&cipher_mode), "Mode can't be streamed");
+    Encryptor {
+      inner,
+      block_operation: block_operation(Operation::Encrypt, &cipher_mode),
+      is_padded: is_padded(&cipher_mode),
+      cipher_mode_impl: cipher.cipher_mode_impl(Operation::Encrypt, cipher_mode),
+      cipher,
+      buffer: Vec::new(),
+      pending: Vec::new(),
+    }
+  }
+
+  fn transform(&mut self, data: &[u8]) -> Vec<u8> {
+    let cipher = &self.cipher;
+    let block_operation = self.block_operation;
+    self.cipher_mode_impl.transform_chunks(data, &|block| {
+      modes::transform(cipher, block, block_operation)
+    })
+  }
+
+  fn write_pending(&mut self) -> io::Result<()> {
+    while !self.pending.is_empty() {
+      match self.inner.write(&self.pending) {
+        Ok(0) => {
+          return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            "Failed to write the ciphertext",
+          ))
+        }
+        Ok(written) => {
+          self.pending.drain(..written);
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+        Err(e) => return Err(e),
+      }
+    }
+    Ok(())
+  }
+
+  // Writes out what's left of the stream and returns the inner writer.
+  pub fn finish(mut self) -> io::Result<W> {
+    self.write_pending()?;
+    let remainder = if self.is_padded {
+      pkcs7::pad(&self.buffer, 16)
+    } else {
+      self.buffer.clone()
+    };
+    let output = self.transform(&remainder);
+    self.inner.write_all(&output)?;
+    self.inner.flush()?;
+    Ok(self.inner)
+  }
+}
+
+impl<W: Write> Write for Encryptor<W> {
+  // Encrypting moves the chaining state on, so once the data has been taken
+  // its ciphertext is kept until the inner writer accepts it. A failure to
+  // write it is reported by the next write, flush or finish, and nothing is
+  // taken from the caller until the earlier ciphertext has been written.
+  fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+    self.write_pending()?;
+    self.buffer.extend_from_slice(data);
+    let whole_blocks = self.buffer.len() / 16 * 16;
+    let blocks: Vec<u8> = self.buffer.drain(..whole_blocks).collect();
+    let output = self.transform(&blocks);
+    self.pending.extend(output);
+    let _ = self.write_pending();
+    Ok(data.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.write_pending()?;
+    self.inner.flush()
+  }
+}
+
+// Decrypts everything read from the inner reader. With a padded mode the last
+// block is held back until the end of the stream so that the padding can be
+// stripped.
+pub struct Decryptor<R: Read> {
+  inner: R,
+  cipher: Cipher,
+  cipher_mode_impl: Box<dyn CipherModeImpl>,
+  block_operation: Operation,
+  is_padded: bool,
+  ciphertext: Vec<u8>,
+  plaintext: Vec<u8>,
+  is_finished: bool,
+  // Why the stream was rejected, reported again by every later read.
+  failure: Option<&'static str>,
+}
+
+impl<R: Read> Decryptor<R> {
+  pub fn new(inner: R, cipher: Cipher, cipher_mode: CipherMode) -> Decryptor<R> {
+    assert!(is_streamable(&cipher_mode), "Mode can't be streamed");
+    Decryptor {
+      inner,
+      block_operation: block_operation(Operation::Decrypt, &cipher_mode),
+      is_padded: is_padded(&cipher_mode),
+      cipher_mode_impl: cipher.cipher_mode_impl(Operation::Decrypt, cipher_mode),
+      cipher,
+      ciphertext: Vec::new(),
+      plaintext: Vec::new(),
+      is_finished: false,
+      failure: None,
+    }
+  }
+
+  fn transform(&mut self, length: usize) -> Vec<u8> {
+    let data: Vec<u8> = self.ciphertext.drain(..length).collect();
+    let cipher = &self.cipher;
+    let block_operation = self.block_operation;
+    self.cipher_mode_impl.transform_chunks(&data, &|block| {
+      modes::transform(cipher, block, block_operation)
+    })
+  }
+
+  fn fail(&mut self, message: &'static str) -> io::Result<()> {
+    self.failure = Some(message);
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+  }
+
+  fn fill_plaintext(&mut self) -> io::Result<()> {
+    let mut chunk = [0; 4096];
+    let read = self.inner.read(&mut chunk)?;
+    if read > 0 {
+      self.ciphertext.extend_from_slice(&chunk[..read]);
+      let available = if self.is_padded {
+        self.ciphertext.len().saturating_sub(1) / 16 * 16
+      } else {
+        self.ciphertext.len() / 16 * 16
+      };
+      let output = self.transform(available);
+      self.plaintext.extend(output);
+      return Ok(());
+    }
+    if self.is_padded {
+      if self.ciphertext.len() != 16 {
+        return self.fail("Ciphertext is not a whole number of blocks");
+      }
+      let mut last_block = self.transform(16);
+      if !pkcs7::is_valid(&last_block, 16) {
+        return self.fail("Final block is not correctly padded");
+      }
+      pkcs7::unpad_mut(&mut last_block, 16);
+      self.plaintext.extend(last_block);
+    } else {
+      let remainder = self.ciphertext.len();
+      let output = self.transform(remainder);
+      self.plaintext.extend(output);
+    }
+    self.is_finished = true;
+    Ok(())
+  }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if let Some(message) = self.failure {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    while self.plaintext.is_empty() && !self.is_finished {
+      self.fill_plaintext()?;
+    }
+    let length = buf.len().min(self.plaintext.len());
+    buf[..length].copy_from_slice(&self.plaintext[..length]);
+    self.plaintext.drain(..length);
+    Ok(length)
+  }
+}
+
+#[test]
+fn stream_large_cbc() {
+  use rand::{thread_rng, Rng};
+  let mut rng = thread_rng();
+  let key = util::gen_random_bytes(&mut rng, 16);
+  let iv: [u8; 16] = rng.gen();
+  let plaintext = util::gen_random_bytes(&mut rng, 10_000);
+  let cipher = Cipher::new(&key);
+
+  let mut encryptor = Encryptor::new(Vec::new(), cipher.clone(), CipherMode::CBC(iv));
+  for chunk in plaintext.chunks(333) {
+    encryptor.write_all(chunk).unwrap();
+  }
+  let ciphertext = encryptor.finish().unwrap();
+  assert_eq!(
+    perform(
+      &pkcs7::pad(&plaintext, 16),
+      &key,
+      Operation::Encrypt,
+      CipherMode::CBC(iv)
+    ),
+    ciphertext
+  );
+
+  let mut decryptor = Decryptor::new(&ciphertext[..], cipher.clone(), CipherMode::CBC(iv));
+  let mut decrypted = Vec::new();
+  decryptor.read_to_end(&mut decrypted).unwrap();
+  assert_eq!(plaintext, decrypted);
+
+  let mut truncated = Decryptor::new(&ciphertext[..100], cipher.clone(), CipherMode::CBC(iv));
+  assert_eq!(
+    io::ErrorKind::InvalidData,
+    truncated.read_to_end(&mut Vec::new()).unwrap_err().kind()
+  );
+
+  // The plaintext fills its last block, so the padding is a whole block of
+  // 16s and this turns its last byte into 17.
+  let mut corrupted = ciphertext.clone();
+  let length = corrupted.len();
+  corrupted[length - 17] ^= 1;
+  let mut corrupted = Decryptor::new(&corrupted[..], cipher, CipherMode::CBC(iv));
+  let mut decrypted = Vec::new();
+  assert_eq!(
+    io::ErrorKind::InvalidData,
+    corrupted.read_to_end(&mut decrypted).unwrap_err().kind()
+  );
+  // The rejected block isn't handed out by a later read, which doesn't look
+  // like the end of the stream either.
+  assert_eq!(plaintext.len() - plaintext.len() % 16, decrypted.len());
+  assert_eq!(
+    io::ErrorKind::InvalidData,
+    corrupted.read(&mut [0; 16]).unwrap_err().kind()
+  );
+}
+
+#[test]
+fn stream_write_failure() {
+  use rand::{thread_rng, Rng};
+  use std::cell::Cell;
+
+  struct FlakyWriter<'a> {
+    data: Vec<u8>,
+    is_failing: &'a Cell<bool>,
+  }
+
+  impl<'a> Write for FlakyWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+      if self.is_failing.get() {
+        return Err(io::Error::other("Write failed"));
+      }
+      self.data.extend_from_slice(data);
+      Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  let mut rng = thread_rng();
+  let key = util::gen_random_bytes(&mut rng, 16);
+  let iv: [u8; 16] = rng.gen();
+  let plaintext = util::gen_random_bytes(&mut rng, 100);
+  let cipher = Cipher::new(&key);
+  let is_failing = Cell::new(true);
+  let writer = FlakyWriter {
+    data: Vec::new(),
+    is_failing: &is_failing,
+  };
+  let mut encryptor = Encryptor::new(writer, cipher, CipherMode::CBC(iv));
+  // The first write is taken even though its ciphertext can't be written yet,
+  // but nothing more is until that ciphertext has been.
+  assert_eq!(
+    Ok(40),
+    encryptor.write(&plaintext[..40]).map_err(|e| e.kind())
+  );
+  assert!(encryptor.write(&plaintext[40..]).is_err());
+  assert!(encryptor.flush().is_err());
+  is_failing.set(false);
+  encryptor.write_all(&plaintext[40..]).unwrap();
+  let writer = encryptor.finish().unwrap();
+  assert_eq!(
+    perform(
+      &pkcs7::pad(&plaintext, 16),
+      &key,
+      Operation::Encrypt,
+      CipherMode::CBC(iv)
+    ),
+    writer.data
+  );
+}
@@ -53,8 +53,12 @@ pub fn sort_keysizes_by_probability(data: &[u8], min_size: usize, max_size: usiz
 }
 
 pub fn likely_aes_ecb_score(data: &[u8]) -> u32 {
-  const CHUNK_SIZE: usize = 16;
-  let chunks: Vec<&[u8]> = data.chunks(CHUNK_SIZE).collect();
+  likely_ecb_score(data, 16)
+}
+
+// The number of pairs of identical blocks.
+pub fn likely_ecb_score(data: &[u8], block_size: usize) -> u32 {
+  let chunks: Vec<&[u8]> = data.chunks(block_size).collect();
   chunks
     .iter()
     .enumerate()
@@ -66,3 +70,38 @@ pub fn likely_aes_ecb_score(data: &[u8]) -> u32 {
     })
     .sum()
 }
+
+// Pairs each block with the first earlier block that's equal to it, in one
+// pass over the data, so that it scales to the tens of millions of blocks it
+// takes for a 64-bit block cipher to repeat.
+pub fn find_block_collisions(data: &[u8], block_size: usize) -> Vec<(usize, usize)> {
+  use std::collections::hash_map::{Entry, HashMap};
+  let mut first_seen = HashMap::with_capacity(data.len() / block_size);
+  let mut collisions = Vec::new();
+  for (i, block) in data.chunks_exact(block_size).enumerate() {
+    match first_seen.entry(block) {
+      Entry::Occupied(entry) => collisions.push((*entry.get(), i)),
+      Entry::Vacant(entry) => {
+        entry.insert(i);
+      }
+    }
+  }
+  collisions
+}
+
+#[test]
+fn test_find_block_collisions() {
+  let data = b"YELLOW SUBMARINEyellow submarineYELLOW SUBMARINEYELLOW SUBMARINE";
+  assert_eq!(vec![(0, 2), (0, 3)], find_block_collisions(data, 16));
+  assert_eq!(3, likely_ecb_score(data, 16));
+  assert_eq!(
+    vec![(0, 4), (1, 5), (0, 6), (1, 7)],
+    find_block_collisions(data, 8)
+  );
+  let data = b"0123456789abcdef0123456789ABCDEF";
+  assert!(find_block_collisions(data, 16).is_empty());
+  // Blocks that only share their first eight bytes don't collide, and don't
+  // hide the ones that do.
+  let data = b"AAAAAAAA1.......AAAAAAAA2.......AAAAAAAA2.......";
+  assert_eq!(vec![(1, 2)], find_block_collisions(data, 16));
+}
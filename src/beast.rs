@@ -0,0 +1,71 @@
+use oracle::{ChainedCBC, Oracle};
+use xor;
+
+// Recovers the secret that follows the known start of every request, with a
+// path inserted at the given offset. The path puts the next secret byte at
+// the end of a block, after fifteen known bytes. A guess for that block is
+// then sent as a record of its own, xored with the IV it is going to get and
+// with the ciphertext block before the target, so that it encrypts to the
+// target block exactly when the guess is right.
+fn recover<F: Fn(&[u8]) -> Vec<u8>>(
+  stream: &ChainedCBC,
+  request: &F,
+  known: &[u8],
+  path_offset: usize,
+  length: usize,
+) -> Vec<u8> {
+  let last_block = |record: &[u8]| record[record.len() - 16..].to_vec();
+  // Every record gives away the IV of the next one.
+  let mut iv = last_block(&stream.encode(b"").data);
+  let mut secret = Vec::new();
+  while secret.len() < length {
+    let path = vec![b'A'; 15 - (known.len() + secret.len()) % 16];
+    let mut plaintext = known[..path_offset].to_vec();
+    plaintext.extend_from_slice(&path);
+    plaintext.extend_from_slice(&known[path_offset..]);
+    plaintext.extend_from_slice(&secret);
+    let mut chained = iv.clone();
+    chained.extend(request(&path));
+    iv = last_block(&chained);
+    let block = plaintext.len() / 16;
+    let previous = chained[16 * block..16 * (block + 1)].to_vec();
+    let target = chained[16 * (block + 1)..16 * (block + 2)].to_vec();
+    let byte = (0..=255)
+      .find(|&guess| {
+        let mut guess_block = plaintext[16 * block..].to_vec();
+        guess_block.push(guess);
+        xor::buffer_mut(&mut guess_block, xor::Key::FullBuffer(&iv));
+        xor::buffer_mut(&mut guess_block, xor::Key::FullBuffer(&previous));
+        let record = stream.encode(&guess_block).data;
+        iv = last_block(&record);
+        record[..16] == target[..]
+      })
+      .unwrap();
+    secret.push(byte);
+  }
+  secret
+}
+
+#[test]
+fn beast() {
+  use oracle::{ConstantAppend, ConstantPrepend};
+  let stream = ChainedCBC::new();
+  let cookie = b"session=d2a84f4b8b650937ec8f73cd";
+
+  // The victim's browser sends requests for any path over the connection,
+  // with its cookie after the path, and also lets the attacker send data of
+  // their own through it.
+  let request = |path: &[u8]| {
+    let mut suffix = b" HTTP/1.1\r\nCookie: ".to_vec();
+    suffix.extend_from_slice(cookie);
+    suffix.extend_from_slice(b"\r\n\r\n");
+    ConstantAppend::new(ConstantPrepend::new(&stream, b"GET /".to_vec()), suffix)
+      .encode(path)
+      .data
+  };
+  let known = b"GET / HTTP/1.1\r\nCookie: ";
+  assert_eq!(
+    cookie.to_vec(),
+    recover(&stream, &request, known, 5, cookie.len())
+  );
+}
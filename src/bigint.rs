@@ -0,0 +1,657 @@
+use rand::Rng;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Rem, Shl, Shr, Sub};
+
+// An arbitrary precision unsigned integer stored as little endian 32 bit
+// limbs without any trailing zero limbs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+  limbs: Vec<u32>,
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+  while limbs.last() == Some(&0) {
+    limbs.pop();
+  }
+}
+
+impl BigUint {
+  fn from_limbs(mut limbs: Vec<u32>) -> BigUint {
+    trim(&mut limbs);
+    BigUint { limbs }
+  }
+
+  pub fn zero() -> BigUint {
+    BigUint { limbs: Vec::new() }
+  }
+
+  pub fn one() -> BigUint {
+    BigUint::from_u64(1)
+  }
+
+  pub fn from_u64(n: u64) -> BigUint {
+    BigUint::from_limbs(vec![n as u32, (n >> 32) as u32])
+  }
+
+  pub fn from_bytes_be(bytes: &[u8]) -> BigUint {
+    let mut limbs = Vec::with_capacity(bytes.len() / 4 + 1);
+    for chunk in bytes.rchunks(4) {
+      limbs.push(
+        chunk
+          .iter()
+          .fold(0u32, |accum, &byte| (accum << 8) | u32::from(byte)),
+      );
+    }
+    BigUint::from_limbs(limbs)
+  }
+
+  pub fn to_bytes_be(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(self.limbs.len() * 4);
+    for limb in self.limbs.iter().rev() {
+      for i in (0..4).rev() {
+        bytes.push((limb >> (8 * i)) as u8);
+      }
+    }
+    let leading_zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    bytes.split_off(leading_zeros)
+  }
+
+  pub fn from_str_radix(s: &str, radix: u32) -> BigUint {
+    let mut n = BigUint::zero();
+    for c in s.chars() {
+      let digit = c
+        .to_digit(radix)
+        .unwrap_or_else(|| panic!("Invalid digit {:?} for radix {}", c, radix));
+      n = n.mul_small(radix).add_small(digit);
+    }
+    n
+  }
+
+  pub fn to_u64(&self) -> Option<u64> {
+    match self.limbs.len() {
+      0 => Some(0),
+      1 => Some(u64::from(self.limbs[0])),
+      2 => Some(u64::from(self.limbs[0]) | (u64::from(self.limbs[1]) << 32)),
+      _ => None,
+    }
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.limbs.is_empty()
+  }
+
+  pub fn is_one(&self) -> bool {
+    self.limbs == [1]
+  }
+
+  pub fn is_even(&self) -> bool {
+    !self.bit(0)
+  }
+
+  pub fn bits(&self) -> usize {
+    match self.limbs.last() {
+      None => 0,
+      Some(last) => 32 * self.limbs.len() - last.leading_zeros() as usize,
+    }
+  }
+
+  pub fn bit(&self, i: usize) -> bool {
+    self
+      .limbs
+      .get(i / 32)
+      .is_some_and(|limb| (limb >> (i % 32)) & 1 == 1)
+  }
+
+  fn mul_small(&self, m: u32) -> BigUint {
+    let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+    let mut carry = 0u64;
+    for &limb in self.limbs.iter() {
+      let product = u64::from(limb) * u64::from(m) + carry;
+      limbs.push(product as u32);
+      carry = product >> 32;
+    }
+    limbs.push(carry as u32);
+    BigUint::from_limbs(limbs)
+  }
+
+  fn add_small(&self, a: u32) -> BigUint {
+    self + &BigUint::from_u64(u64::from(a))
+  }
+
+  // Returns the quotient and remainder.
+  fn divrem_small(&self, d: u32) -> (BigUint, u32) {
+    let mut quotient = vec![0; self.limbs.len()];
+    let mut remainder = 0u64;
+    for i in (0..self.limbs.len()).rev() {
+      let current = (remainder << 32) | u64::from(self.limbs[i]);
+      quotient[i] = (current / u64::from(d)) as u32;
+      remainder = current % u64::from(d);
+    }
+    (BigUint::from_limbs(quotient), remainder as u32)
+  }
+
+  // Knuth's algorithm D.
+  pub fn divrem(&self, divisor: &BigUint) -> (BigUint, BigUint) {
+    assert!(!divisor.is_zero(), "Division by zero");
+    if self < divisor {
+      return (BigUint::zero(), self.clone());
+    }
+    if divisor.limbs.len() == 1 {
+      let (quotient, remainder) = self.divrem_small(divisor.limbs[0]);
+      return (quotient, BigUint::from_u64(u64::from(remainder)));
+    }
+    const BASE: u64 = 1 << 32;
+    let shift = divisor.limbs.last().unwrap().leading_zeros() as usize;
+    let v = (divisor << shift).limbs;
+    let mut u = (self << shift).limbs;
+    if u.len() == self.limbs.len() {
+      u.push(0);
+    }
+    let n = v.len();
+    let m = u.len() - n - 1;
+    let mut quotient = vec![0u32; m + 1];
+    for j in (0..m + 1).rev() {
+      let numerator = (u64::from(u[j + n]) << 32) | u64::from(u[j + n - 1]);
+      let mut q_hat = numerator / u64::from(v[n - 1]);
+      let mut r_hat = numerator % u64::from(v[n - 1]);
+      while q_hat >= BASE || q_hat * u64::from(v[n - 2]) > ((r_hat << 32) | u64::from(u[j + n - 2]))
+      {
+        q_hat -= 1;
+        r_hat += u64::from(v[n - 1]);
+        if r_hat >= BASE {
+          break;
+        }
+      }
+      let mut borrow = 0i64;
+      let mut carry = 0u64;
+      for i in 0..n {
+        let product = q_hat * u64::from(v[i]) + carry;
+        carry = product >> 32;
+        let t = i64::from(u[i + j]) - borrow - (product & 0xffff_ffff) as i64;
+        u[i + j] = t as u32;
+        borrow = if t < 0 { 1 } else { 0 };
+      }
+      let t = i64::from(u[j + n]) - borrow - carry as i64;
+      u[j + n] = t as u32;
+      if t < 0 {
+        // q_hat was one too large, add the divisor back
+        q_hat -= 1;
+        let mut carry = 0u64;
+        for i in 0..n {
+          let sum = u64::from(u[i + j]) + u64::from(v[i]) + carry;
+          u[i + j] = sum as u32;
+          carry = sum >> 32;
+        }
+        u[j + n] = u[j + n].wrapping_add(carry as u32);
+      }
+      quotient[j] = q_hat as u32;
+    }
+    u.truncate(n);
+    (
+      BigUint::from_limbs(quotient),
+      BigUint::from_limbs(u) >> shift,
+    )
+  }
+
+  pub fn add_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+    &(self + other) % modulus
+  }
+
+  pub fn sub_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+    let a = self % modulus;
+    let b = other % modulus;
+    if a >= b {
+      &a - &b
+    } else {
+      &(&a + modulus) - &b
+    }
+  }
+
+  pub fn mul_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+    &(self * other) % modulus
+  }
+
+  pub fn modpow(&self, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    let mut result = &BigUint::one() % modulus;
+    let base = self % modulus;
+    for i in (0..exponent.bits()).rev() {
+      result = result.mul_mod(&result, modulus);
+      if exponent.bit(i) {
+        result = result.mul_mod(&base, modulus);
+      }
+    }
+    result
+  }
+
+  pub fn gcd(&self, other: &BigUint) -> BigUint {
+    let mut a = self.clone();
+    let mut b = other.clone();
+    while !b.is_zero() {
+      let r = &a % &b;
+      a = b;
+      b = r;
+    }
+    a
+  }
+
+  // Extended Euclid, keeping the Bézout coefficient reduced modulo the
+  // modulus so it never goes negative.
+  pub fn modinv(&self, modulus: &BigUint) -> Option<BigUint> {
+    let mut old_r = modulus.clone();
+    let mut r = self % modulus;
+    let mut old_s = BigUint::zero();
+    let mut s = BigUint::one();
+    while !r.is_zero() {
+      let (q, new_r) = old_r.divrem(&r);
+      let new_s = old_s.sub_mod(&q.mul_mod(&s, modulus), modulus);
+      old_r = r;
+      r = new_r;
+      old_s = s;
+      s = new_s;
+    }
+    if old_r.is_one() {
+      Some(old_s)
+    } else {
+      None
+    }
+  }
+
+  // Tonelli-Shanks; the modulus must be an odd prime.
+  pub fn mod_sqrt(&self, p: &BigUint) -> Option<BigUint> {
+    let one = BigUint::one();
+    let a = self % p;
+    if a.is_zero() {
+      return Some(a);
+    }
+    let p_minus_one = p - &one;
+    if !a.modpow(&(&p_minus_one >> 1), p).is_one() {
+      return None;
+    }
+    let mut s = 0;
+    let mut q = p_minus_one.clone();
+    while q.is_even() {
+      q = q >> 1;
+      s += 1;
+    }
+    let mut z = BigUint::from_u64(2);
+    while z.modpow(&(&p_minus_one >> 1), p) != p_minus_one {
+      z = z.add_small(1);
+    }
+    let mut m = s;
+    let mut c = z.modpow(&q, p);
+    let mut t = a.modpow(&q, p);
+    let mut r = a.modpow(&(&(&q + &one) >> 1), p);
+    while !t.is_one() {
+      let mut i = 0;
+      let mut t_power = t.clone();
+      while !t_power.is_one() {
+        t_power = t_power.mul_mod(&t_power, p);
+        i += 1;
+      }
+      let mut b = c.clone();
+      for _ in 0..m - i - 1 {
+        b = b.mul_mod(&b, p);
+      }
+      m = i;
+      c = b.mul_mod(&b, p);
+      t = t.mul_mod(&c, p);
+      r = r.mul_mod(&b, p);
+    }
+    Some(r)
+  }
+
+  // The distinct prime factors below the bound, found by trial division.
+  pub fn small_prime_factors(&self, bound: u32) -> Vec<u32> {
+    let mut factors = Vec::new();
+    let mut n = self.clone();
+    for d in 2..bound {
+      if n.is_one() {
+        break;
+      }
+      let mut is_factor = false;
+      loop {
+        let (quotient, remainder) = n.divrem_small(d);
+        if remainder != 0 {
+          break;
+        }
+        n = quotient;
+        is_factor = true;
+      }
+      if is_factor {
+        factors.push(d);
+      }
+    }
+    factors
+  }
+
+  // Uniformly random in [0, bound).
+  pub fn random_below<R: Rng>(rng: &mut R, bound: &BigUint) -> BigUint {
+    assert!(!bound.is_zero());
+    let bits = bound.bits();
+    loop {
+      let mut limbs: Vec<u32> = (0..bits.div_ceil(32)).map(|_| rng.gen()).collect();
+      let excess = 32 * limbs.len() - bits;
+      let last = limbs.len() - 1;
+      limbs[last] &= u32::MAX >> excess;
+      let candidate = BigUint::from_limbs(limbs);
+      if &candidate < bound {
+        return candidate;
+      }
+    }
+  }
+}
+
+// Combines (residue, modulus) pairs with pairwise coprime moduli into the
+// unique residue modulo their product, which is returned alongside it.
+pub fn crt(residues: &[(BigUint, BigUint)]) -> (BigUint, BigUint) {
+  let modulus = residues
+    .iter()
+    .fold(BigUint::one(), |accum, (_, m)| &accum * m);
+  let mut result = BigUint::zero();
+  for (residue, m) in residues.iter() {
+    let others = &modulus / m;
+    let inverse = others.modinv(m).expect("Moduli must be coprime");
+    let term = residue.mul_mod(&inverse, m).mul_mod(&others, &modulus);
+    result = result.add_mod(&term, &modulus);
+  }
+  (result, modulus)
+}
+
+impl Ord for BigUint {
+  fn cmp(&self, other: &BigUint) -> Ordering {
+    self
+      .limbs
+      .len()
+      .cmp(&other.limbs.len())
+      .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+  }
+}
+
+impl PartialOrd for BigUint {
+  fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Display for BigUint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.is_zero() {
+      return write!(f, "0");
+    }
+    let mut chunks = Vec::new();
+    let mut n = self.clone();
+    while !n.is_zero() {
+      let (quotient, remainder) = n.divrem_small(1_000_000_000);
+      chunks.push(remainder);
+      n = quotient;
+    }
+    write!(f, "{}", chunks.pop().unwrap())?;
+    for chunk in chunks.iter().rev() {
+      write!(f, "{:09}", chunk)?;
+    }
+    Ok(())
+  }
+}
+
+impl Add<&BigUint> for &BigUint {
+  type Output = BigUint;
+  fn add(self, other: &BigUint) -> BigUint {
+    let len = self.limbs.len().max(other.limbs.len());
+    let mut limbs = Vec::with_capacity(len + 1);
+    let mut carry = 0u64;
+    for i in 0..len {
+      let sum = u64::from(*self.limbs.get(i).unwrap_or(&0))
+        + u64::from(*other.limbs.get(i).unwrap_or(&0))
+        + carry;
+      limbs.push(sum as u32);
+      carry = sum >> 32;
+    }
+    limbs.push(carry as u32);
+    BigUint::from_limbs(limbs)
+  }
+}
+
+impl Sub<&BigUint> for &BigUint {
+  type Output = BigUint;
+  fn sub(self, other: &BigUint) -> BigUint {
+    assert!(self >= other, "Subtraction would underflow");
+    let mut limbs = Vec::with_capacity(self.limbs.len());
+    let mut borrow = 0i64;
+    for i in 0..self.limbs.len() {
+      let difference =
+        i64::from(self.limbs[i]) - i64::from(*other.limbs.get(i).unwrap_or(&0)) - borrow;
+      limbs.push(difference as u32);
+      borrow = if difference < 0 { 1 } else { 0 };
+    }
+    BigUint::from_limbs(limbs)
+  }
+}
+
+impl Mul<&BigUint> for &BigUint {
+  type Output = BigUint;
+  fn mul(self, other: &BigUint) -> BigUint {
+    if self.is_zero() || other.is_zero() {
+      return BigUint::zero();
+    }
+    let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+    for (i, &a) in self.limbs.iter().enumerate() {
+      let mut carry = 0u64;
+      for (j, &b) in other.limbs.iter().enumerate() {
+        let product = u64::from(a) * u64::from(b) + u64::from(limbs[i + j]) + carry;
+        limbs[i + j] = product as u32;
+        carry = product >> 32;
+      }
+      limbs[i + other.limbs.len()] = carry as u32;
+    }
+    BigUint::from_limbs(limbs)
+  }
+}
+
+impl Div<&BigUint> for &BigUint {
+  type Output = BigUint;
+  fn div(self, other: &BigUint) -> BigUint {
+    self.divrem(other).0
+  }
+}
+
+impl Rem<&BigUint> for &BigUint {
+  type Output = BigUint;
+  fn rem(self, other: &BigUint) -> BigUint {
+    self.divrem(other).1
+  }
+}
+
+impl Shl<usize> for &BigUint {
+  type Output = BigUint;
+  fn shl(self, shift: usize) -> BigUint {
+    let limb_shift = shift / 32;
+    let bit_shift = shift % 32;
+    let mut limbs = vec![0u32; limb_shift];
+    if bit_shift == 0 {
+      limbs.extend_from_slice(&self.limbs);
+    } else {
+      let mut carry = 0u32;
+      for &limb in self.limbs.iter() {
+        limbs.push((limb << bit_shift) | carry);
+        carry = limb >> (32 - bit_shift);
+      }
+      limbs.push(carry);
+    }
+    BigUint::from_limbs(limbs)
+  }
+}
+
+impl Shr<usize> for &BigUint {
+  type Output = BigUint;
+  fn shr(self, shift: usize) -> BigUint {
+    let limb_shift = shift / 32;
+    let bit_shift = shift % 32;
+    if limb_shift >= self.limbs.len() {
+      return BigUint::zero();
+    }
+    let source = &self.limbs[limb_shift..];
+    let limbs = if bit_shift == 0 {
+      source.to_vec()
+    } else {
+      (0..source.len())
+        .map(|i| {
+          let high = source.get(i + 1).map_or(0, |next| next << (32 - bit_shift));
+          (source[i] >> bit_shift) | high
+        })
+        .collect()
+    };
+    BigUint::from_limbs(limbs)
+  }
+}
+
+// By value variants, for convenience when chaining expressions.
+macro_rules! forward_by_value {
+  ($imp:ident, $method:ident) => {
+    impl $imp<BigUint> for BigUint {
+      type Output = BigUint;
+      fn $method(self, other: BigUint) -> BigUint {
+        (&self).$method(&other)
+      }
+    }
+  };
+}
+
+forward_by_value!(Add, add);
+forward_by_value!(Sub, sub);
+forward_by_value!(Mul, mul);
+forward_by_value!(Div, div);
+forward_by_value!(Rem, rem);
+
+impl Shl<usize> for BigUint {
+  type Output = BigUint;
+  fn shl(self, shift: usize) -> BigUint {
+    &self << shift
+  }
+}
+
+impl Shr<usize> for BigUint {
+  type Output = BigUint;
+  fn shr(self, shift: usize) -> BigUint {
+    &self >> shift
+  }
+}
+
+#[cfg(test)]
+fn big(s: &str) -> BigUint {
+  BigUint::from_str_radix(s, 10)
+}
+
+#[test]
+fn test_display_round_trip() {
+  let s = "233970423115425145524320034830162017933";
+  assert_eq!(s, big(s).to_string());
+  assert_eq!("0", BigUint::zero().to_string());
+  assert_eq!("1000000000", BigUint::from_u64(1_000_000_000).to_string());
+  assert_eq!(big("4294967296"), BigUint::from_str_radix("100000000", 16));
+}
+
+#[test]
+fn test_bytes_round_trip() {
+  let n = big("233970423115425145524320034830162017933");
+  assert_eq!(n, BigUint::from_bytes_be(&n.to_bytes_be()));
+  assert_eq!(vec![1, 0], BigUint::from_u64(256).to_bytes_be());
+  assert!(BigUint::zero().to_bytes_be().is_empty());
+}
+
+#[test]
+fn test_divrem() {
+  let a = big("233970423115425145524320034830162017933233970423115425145524320034830162017933");
+  let b = big("29246302889428143187362802287225875743");
+  let (q, r) = a.divrem(&b);
+  assert!(r < b);
+  assert_eq!(a, &(&q * &b) + &r);
+}
+
+#[test]
+fn test_modpow() {
+  // Fermat's little theorem
+  let p = big("233970423115425145524320034830162017933");
+  let a = big("182");
+  assert_eq!(BigUint::one(), a.modpow(&(&p - &BigUint::one()), &p));
+  assert_eq!(
+    BigUint::from_u64(445),
+    BigUint::from_u64(4).modpow(&BigUint::from_u64(13), &BigUint::from_u64(497))
+  );
+}
+
+#[test]
+fn test_modinv() {
+  let p = big("233970423115425145524320034830162017933");
+  let a = big("85518893674295321206118380980485522083");
+  assert_eq!(BigUint::one(), a.mul_mod(&a.modinv(&p).unwrap(), &p));
+  assert_eq!(None, BigUint::from_u64(6).modinv(&BigUint::from_u64(9)));
+}
+
+#[test]
+fn test_mod_sqrt() {
+  use rand::thread_rng;
+  // p = 5 (mod 8) exercises the general Tonelli-Shanks loop
+  let p = big("233970423115425145524320034830162017933");
+  let mut rng = thread_rng();
+  for _ in 0..10 {
+    let x = BigUint::random_below(&mut rng, &p);
+    let square = x.mul_mod(&x, &p);
+    let root = square.mod_sqrt(&p).unwrap();
+    assert_eq!(square, root.mul_mod(&root, &p));
+  }
+}
+
+#[test]
+fn test_crt() {
+  let residues = [
+    (BigUint::from_u64(2), BigUint::from_u64(3)),
+    (BigUint::from_u64(3), BigUint::from_u64(5)),
+    (BigUint::from_u64(2), BigUint::from_u64(7)),
+  ];
+  assert_eq!(
+    (BigUint::from_u64(23), BigUint::from_u64(105)),
+    crt(&residues)
+  );
+}
+
+#[test]
+fn test_small_prime_factors() {
+  assert_eq!(
+    vec![2, 3, 11, 23, 31, 89, 4999, 28411, 45361],
+    big("233970423115425145550826547352470124412").small_prime_factors(1 << 16)
+  );
+}
+
+#[cfg(test)]
+mod qctests {
+  use super::BigUint;
+  quickcheck! {
+    fn matches_u128(a: u64, b: u64, c: u64) -> bool {
+      let (big_a, big_b, big_c) = (BigUint::from_u64(a), BigUint::from_u64(b), BigUint::from_u64(c));
+      let product = u128::from(a) * u128::from(b);
+      let expected = |n: u128| BigUint::from_bytes_be(&n.to_be_bytes());
+      let sum = u128::from(a) + u128::from(b);
+      (&big_a * &big_b) == expected(product)
+        && (&big_a + &big_b) == expected(sum)
+        && (c == 0 || (&(&big_a * &big_b) % &big_c) == expected(product % u128::from(c)))
+        && (c == 0 || (&(&big_a * &big_b) / &big_c) == expected(product / u128::from(c)))
+    }
+
+    fn divrem(a: Vec<u32>, b: Vec<u32>) -> bool {
+      let a = BigUint::from_limbs(a);
+      let b = BigUint::from_limbs(b);
+      if b.is_zero() {
+        return true;
+      }
+      let (q, r) = a.divrem(&b);
+      r < b && a == &(&q * &b) + &r
+    }
+
+    fn shifts(a: Vec<u32>, shift: u8) -> bool {
+      let a = BigUint::from_limbs(a);
+      let shift = shift as usize;
+      &(&a << shift) >> shift == a
+    }
+  }
+}
@@ -0,0 +1,105 @@
+use aes;
+use xor;
+
+// Multiplication by x in GF(2^128) as CMAC uses it: a big endian shift left,
+// reduced modulo x^128 + x^7 + x^2 + x + 1.
+pub fn dbl(block: &[u8; 16]) -> [u8; 16] {
+  let n = u128::from_be_bytes(*block);
+  let reduction = if n >> 127 == 1 { 0x87 } else { 0 };
+  ((n << 1) ^ reduction).to_be_bytes()
+}
+
+// The masks for a final block that is whole and one that had to be padded.
+pub fn subkeys(cipher: &aes::Cipher) -> ([u8; 16], [u8; 16]) {
+  let k1 = dbl(&cipher.encrypt_block(&[0; 16]));
+  let k2 = dbl(&k1);
+  (k1, k2)
+}
+
+// RFC 4493 CMAC, also known as OMAC1: CBC-MAC with a zero IV whose last block
+// is masked with one of the subkeys, so that no tag can be extended into the
+// tag of a longer message.
+pub fn cmac(cipher: &aes::Cipher, message: &[u8]) -> [u8; 16] {
+  let (k1, k2) = subkeys(cipher);
+  let whole_blocks = message.len().saturating_sub(1) / 16;
+  let mut x = [0; 16];
+  for chunk in message[..16 * whole_blocks].chunks(16) {
+    xor::buffer_mut(&mut x, xor::Key::FullBuffer(chunk));
+    x = cipher.encrypt_block(&x);
+  }
+  let tail = &message[16 * whole_blocks..];
+  let mut last = [0; 16];
+  last[..tail.len()].copy_from_slice(tail);
+  if tail.len() == 16 {
+    xor::buffer_mut(&mut last, xor::Key::FullBuffer(&k1));
+  } else {
+    last[tail.len()] = 0x80;
+    xor::buffer_mut(&mut last, xor::Key::FullBuffer(&k2));
+  }
+  xor::buffer_mut(&mut x, xor::Key::FullBuffer(&last));
+  cipher.encrypt_block(&x)
+}
+
+pub fn aes_cmac(key: &[u8], message: &[u8]) -> [u8; 16] {
+  cmac(&aes::Cipher::new(key), message)
+}
+
+// The raw CBC-MAC, only secure for messages of a single fixed length.
+pub fn cbc_mac(key: &[u8], message: &[u8]) -> [u8; 16] {
+  assert!(
+    message.len().is_multiple_of(16),
+    "CBC-MAC needs whole blocks, got {} bytes",
+    message.len()
+  );
+  let cipher = aes::Cipher::new(key);
+  message.chunks(16).fold([0; 16], |mut x, chunk| {
+    xor::buffer_mut(&mut x, xor::Key::FullBuffer(chunk));
+    cipher.encrypt_block(&x)
+  })
+}
+
+#[test]
+fn rfc4493_test_vectors() {
+  use util::parse_byte_string;
+  let key = parse_byte_string("2b7e151628aed2a6abf7158809cf4f3c");
+  let (k1, k2) = subkeys(&aes::Cipher::new(&key));
+  assert_eq!(parse_byte_string("fbeed618357133667c85e08f7236a8de"), k1);
+  assert_eq!(parse_byte_string("f7ddac306ae266ccf90bc11ee46d513b"), k2);
+  let message = parse_byte_string("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e5130c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710");
+  let vectors = [
+    (0, "bb1d6929e95937287fa37d129b756746"),
+    (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+    (40, "dfa66747de9ae63030ca32611497c827"),
+    (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+  ];
+  for &(length, tag) in vectors.iter() {
+    assert_eq!(parse_byte_string(tag), aes_cmac(&key, &message[..length]));
+  }
+}
+
+#[test]
+fn length_extension_forgery() {
+  use rand::thread_rng;
+  use util;
+  let key = util::gen_random_bytes(&mut thread_rng(), 16);
+  let first = b"to=bob&amount=10";
+  let second = b"to=mallory&amount=1000000&memo=x";
+
+  // Given the tags of two messages, the first message followed by the second
+  // with its first block xored with the first tag chains back into the state
+  // the second message starts from, so it carries the second tag.
+  let forge = |first_tag: &[u8; 16]| {
+    let mut forged = first.to_vec();
+    forged.extend(xor::buffer(&second[..16], xor::Key::FullBuffer(first_tag)));
+    forged.extend_from_slice(&second[16..]);
+    forged
+  };
+
+  let forged = forge(&cbc_mac(&key, first));
+  assert_eq!(cbc_mac(&key, second), cbc_mac(&key, &forged));
+
+  // CMAC masks the last block of every message with a secret subkey, so the
+  // tag of the first message is not the chaining value the forgery needs.
+  let forged = forge(&aes_cmac(&key, first));
+  assert_ne!(aes_cmac(&key, second), aes_cmac(&key, &forged));
+}
@@ -0,0 +1,123 @@
+use aes;
+use oracle;
+use std::collections::HashSet;
+
+// MixColumns spreads a single byte difference e in row r of a column into the
+// differences MATRIX[i][r] * e down the rows i of that column.
+const MATRIX: [[u8; 4]; 4] = [[2, 3, 1, 1], [1, 2, 3, 1], [1, 1, 2, 3], [3, 1, 1, 2]];
+
+// Where the byte in row i of column c of the state before the last round ends
+// up in the ciphertext once ShiftRows has moved it.
+fn ciphertext_index(column: usize, row: usize) -> usize {
+  4 * ((column + 4 - row) % 4) + row
+}
+
+// The candidates for the four bytes of the last round key covering one column
+// that are consistent with a correct and faulty ciphertext pair, assuming the
+// fault hit a single byte of that column before the ninth round's
+// MixColumns.
+fn column_candidates(correct: &[u8; 16], faulty: &[u8; 16], column: usize) -> HashSet<[u8; 4]> {
+  // For each row, the key bytes grouped by the difference they imply before
+  // the final S-box.
+  let by_difference: Vec<Vec<Vec<u8>>> = (0..4)
+    .map(|row| {
+      let index = ciphertext_index(column, row);
+      let mut groups = vec![Vec::new(); 256];
+      for k in 0..=255u8 {
+        let difference = aes::INV_SBOX[(correct[index] ^ k) as usize]
+          ^ aes::INV_SBOX[(faulty[index] ^ k) as usize];
+        groups[difference as usize].push(k);
+      }
+      groups
+    })
+    .collect();
+
+  let mut candidates = HashSet::new();
+  for fault_row in 0..4 {
+    for e in 1..=255u8 {
+      let keys: Vec<&Vec<u8>> = (0..4)
+        .map(|row| &by_difference[row][aes::gmul(MATRIX[row][fault_row], e) as usize])
+        .collect();
+      if keys.iter().any(|k| k.is_empty()) {
+        continue;
+      }
+      for &k0 in keys[0].iter() {
+        for &k1 in keys[1].iter() {
+          for &k2 in keys[2].iter() {
+            for &k3 in keys[3].iter() {
+              candidates.insert([k0, k1, k2, k3]);
+            }
+          }
+        }
+      }
+    }
+  }
+  candidates
+}
+
+// Piret-Quisquater: every faulty pair narrows down the four bytes of the last
+// round key behind the column that the fault landed in, and two pairs per
+// column are usually enough to pin them down. The master key then follows by
+// running the key schedule backwards.
+pub fn recover_key(o: &oracle::FaultyAES128) -> Vec<u8> {
+  let mut columns: Vec<Option<HashSet<[u8; 4]>>> = vec![None; 4];
+  let mut plaintext = [0u8; 16];
+  while columns
+    .iter()
+    .any(|c| c.as_ref().is_none_or(|c| c.len() != 1))
+  {
+    let (correct, faulty) = o.encrypt(&plaintext);
+    plaintext[0] = plaintext[0].wrapping_add(1);
+    // The fault reaches exactly the four ciphertext bytes of one column.
+    let column = match (0..4).find(|&column| {
+      (0..4).all(|row| {
+        let index = ciphertext_index(column, row);
+        correct[index] != faulty[index]
+      })
+    }) {
+      Some(column) => column,
+      None => continue,
+    };
+    let candidates = column_candidates(&correct, &faulty, column);
+    columns[column] = Some(match columns[column].take() {
+      None => candidates,
+      Some(previous) => previous.intersection(&candidates).cloned().collect(),
+    });
+    assert!(
+      !columns[column].as_ref().unwrap().is_empty(),
+      "No key is consistent with the faults in column {}",
+      column
+    );
+  }
+
+  let mut last_round_key = vec![0; 16];
+  for (column, candidates) in columns.iter().enumerate() {
+    let key = candidates.as_ref().unwrap().iter().next().unwrap();
+    for (row, &byte) in key.iter().enumerate() {
+      last_round_key[ciphertext_index(column, row)] = byte;
+    }
+  }
+  aes::invert_key_schedule(&last_round_key, 10)
+}
+
+#[test]
+fn test_ciphertext_index() {
+  // Rows shift left by their index.
+  assert_eq!(
+    vec![0, 13, 10, 7],
+    (0..4)
+      .map(|row| ciphertext_index(0, row))
+      .collect::<Vec<_>>()
+  );
+}
+
+#[test]
+fn piret_quisquater() {
+  let o = oracle::FaultyAES128::new();
+  let key = recover_key(&o);
+  let plaintext = [0x42; 16];
+  assert_eq!(
+    o.encrypt(&plaintext).0,
+    aes::Cipher::new(&key).encrypt_block(&plaintext)
+  );
+}
@@ -0,0 +1,231 @@
+use bigint::BigUint;
+use rand::Rng;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// What the solvers below need from a cyclic group, written multiplicatively.
+pub trait Group {
+  type Element: Clone + Eq + Hash;
+
+  fn identity(&self) -> Self::Element;
+  fn combine(&self, a: &Self::Element, b: &Self::Element) -> Self::Element;
+  fn inverse(&self, element: &Self::Element) -> Self::Element;
+  fn power(&self, element: &Self::Element, exponent: &BigUint) -> Self::Element;
+  fn random_element<R: Rng>(&self, rng: &mut R) -> Self::Element;
+}
+
+// The integers modulo a prime p under multiplication.
+#[derive(Clone, Debug)]
+pub struct MultiplicativeGroup {
+  pub p: BigUint,
+}
+
+impl MultiplicativeGroup {
+  pub fn new(p: BigUint) -> MultiplicativeGroup {
+    MultiplicativeGroup { p }
+  }
+}
+
+impl Group for MultiplicativeGroup {
+  type Element = BigUint;
+
+  fn identity(&self) -> BigUint {
+    BigUint::one()
+  }
+
+  fn combine(&self, a: &BigUint, b: &BigUint) -> BigUint {
+    a.mul_mod(b, &self.p)
+  }
+
+  fn inverse(&self, element: &BigUint) -> BigUint {
+    element.modinv(&self.p).unwrap()
+  }
+
+  fn power(&self, element: &BigUint, exponent: &BigUint) -> BigUint {
+    element.modpow(exponent, &self.p)
+  }
+
+  fn random_element<R: Rng>(&self, rng: &mut R) -> BigUint {
+    let one = BigUint::one();
+    &BigUint::random_below(rng, &(&self.p - &one)) + &one
+  }
+}
+
+// Finds x in [0, bound) with g^x = h using about 2 * sqrt(bound) group
+// operations and sqrt(bound) memory.
+pub fn baby_step_giant_step<G: Group>(
+  group: &G,
+  g: &G::Element,
+  h: &G::Element,
+  bound: u64,
+) -> Option<u64> {
+  let m = (bound as f64).sqrt().ceil() as u64;
+  let mut baby_steps = HashMap::new();
+  let mut power = group.identity();
+  for j in 0..m {
+    baby_steps.entry(power.clone()).or_insert(j);
+    power = group.combine(&power, g);
+  }
+  // power is now g^m
+  let giant_step = group.inverse(&power);
+  let mut gamma = h.clone();
+  for i in 0..m {
+    if let Some(&j) = baby_steps.get(&gamma) {
+      let x = i * m + j;
+      return if x < bound { Some(x) } else { None };
+    }
+    gamma = group.combine(&gamma, &giant_step);
+  }
+  None
+}
+
+// Pollard's kangaroo: finds x in [a, b] with g^x = y using about sqrt(b - a)
+// group operations and constant memory.
+pub fn kangaroo<G: Group>(
+  group: &G,
+  g: &G::Element,
+  y: &G::Element,
+  a: u64,
+  b: u64,
+) -> Option<u64> {
+  assert!(a <= b);
+  let width = b - a;
+  // Pick k so that the mean jump, (2^k - 1) / k, is around sqrt(width) / 2.
+  let target = (width as f64).sqrt() / 2.0;
+  let mut k = 1;
+  while k < 63 && ((1u64 << k) - 1) as f64 / (k as f64) < target {
+    k += 1;
+  }
+  let mean_jump = ((1u64 << k) - 1) / k;
+  let tame_jumps = 4 * mean_jump.max(1);
+  let jump_powers: Vec<G::Element> = (0..k)
+    .map(|i| group.power(g, &BigUint::from_u64(1 << i)))
+    .collect();
+  let step = |element: &G::Element, distance: u64| -> G::Element {
+    group.combine(element, &jump_powers[distance.trailing_zeros() as usize])
+  };
+  let digest = |element: &G::Element| -> u64 {
+    let mut hasher = DefaultHasher::new();
+    element.hash(&mut hasher);
+    hasher.finish()
+  };
+
+  // A walk can miss the trap, so each retry uses a different jump function.
+  for attempt in 0..8 {
+    let jump = |element: &G::Element| -> u64 { 1 << ((digest(element) % k + attempt) % k) };
+
+    // The tame kangaroo starts at the top of the range and sets a trap where
+    // it stops.
+    let mut tame_distance = 0u64;
+    let mut tame = group.power(g, &BigUint::from_u64(b));
+    for _ in 0..tame_jumps {
+      let distance = jump(&tame);
+      tame_distance += distance;
+      tame = step(&tame, distance);
+    }
+
+    // The wild kangaroo starts at y and either lands in the trap or overtakes
+    // it without being caught.
+    let mut wild_distance = 0u64;
+    let mut wild = y.clone();
+    while wild_distance <= width + tame_distance {
+      if wild == tame {
+        return Some(b + tame_distance - wild_distance);
+      }
+      let distance = jump(&wild);
+      wild_distance += distance;
+      wild = step(&wild, distance);
+    }
+  }
+  None
+}
+
+// Pohlig-Hellman subgroup confinement: sends the oracle an element of order
+// r, a prime dividing the order of the group, and finds the secret exponent
+// x modulo r by comparing what comes back with the MAC of each power of that
+// element in turn, since h^x can only take r values.
+pub fn confined_residue<G, R, O, M>(
+  group: &G,
+  order: &BigUint,
+  r: u32,
+  rng: &mut R,
+  oracle: O,
+  mac: M,
+) -> Option<u32>
+where
+  G: Group,
+  R: Rng,
+  O: Fn(&G::Element) -> Vec<u8>,
+  M: Fn(&G::Element) -> Vec<u8>,
+{
+  let cofactor = order / &BigUint::from_u64(u64::from(r));
+  let identity = group.identity();
+  let h = loop {
+    let h = group.power(&group.random_element(rng), &cofactor);
+    if h != identity {
+      break h;
+    }
+  };
+  let response = oracle(&h);
+  let mut guess = identity;
+  for x in 0..r {
+    if mac(&guess) == response {
+      return Some(x);
+    }
+    guess = group.combine(&guess, &h);
+  }
+  None
+}
+
+#[cfg(test)]
+fn smooth_group() -> (MultiplicativeGroup, BigUint) {
+  // p - 1 = 2^2 * 3 * 5 * 7 * 11 * 13 * 17 * 19 * 23 * 29 * 31 and 2 generates
+  // the whole multiplicative group.
+  (
+    MultiplicativeGroup::new(BigUint::from_u64(401_120_980_261)),
+    BigUint::from_u64(2),
+  )
+}
+
+#[test]
+fn test_baby_step_giant_step() {
+  let group = MultiplicativeGroup::new(BigUint::from_u64(1_000_003));
+  let g = BigUint::from_u64(2);
+  for &x in [0, 1, 999, 123_456].iter() {
+    let h = group.power(&g, &BigUint::from_u64(x));
+    assert_eq!(Some(x), baby_step_giant_step(&group, &g, &h, 1 << 20));
+  }
+  let h = group.power(&g, &BigUint::from_u64(5000));
+  assert_eq!(None, baby_step_giant_step(&group, &g, &h, 1000));
+}
+
+#[test]
+fn test_kangaroo() {
+  use rand::{thread_rng, Rng};
+  let (group, g) = smooth_group();
+  let mut rng = thread_rng();
+  let a = 1 << 30;
+  let b = a + (1 << 24);
+  for _ in 0..4 {
+    let x = a + u64::from(rng.gen_range(0u32, (b - a) as u32 + 1));
+    let y = group.power(&g, &BigUint::from_u64(x));
+    assert_eq!(Some(x), kangaroo(&group, &g, &y, a, b));
+  }
+}
+
+#[test]
+fn test_confined_residue() {
+  use rand::thread_rng;
+  let (group, _) = smooth_group();
+  let order = &group.p - &BigUint::one();
+  let mut rng = thread_rng();
+  let x = BigUint::random_below(&mut rng, &order);
+  let oracle = |h: &BigUint| group.power(h, &x).to_bytes_be();
+  let mac = |h: &BigUint| h.to_bytes_be();
+  for r in order.small_prime_factors(1 << 8) {
+    let residue = confined_residue(&group, &order, r, &mut rng, oracle, mac);
+    let expected = (&x % &BigUint::from_u64(u64::from(r))).to_u64().unwrap();
+    assert_eq!(Some(expected as u32), residue);
+  }
+}
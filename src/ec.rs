@@ -0,0 +1,393 @@
+use bigint::BigUint;
+use dlog;
+use rand::Rng;
+use sha1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Point {
+  Infinity,
+  Affine(BigUint, BigUint),
+}
+
+// y^2 = x^3 + ax + b over the integers modulo the prime p. Note that b is
+// never used by the group law, only when checking or generating points.
+#[derive(Clone, Debug)]
+pub struct WeierstrassCurve {
+  pub a: BigUint,
+  pub b: BigUint,
+  pub p: BigUint,
+}
+
+impl WeierstrassCurve {
+  pub fn new(a: BigUint, b: BigUint, p: BigUint) -> WeierstrassCurve {
+    WeierstrassCurve { a, b, p }
+  }
+
+  fn rhs(&self, x: &BigUint) -> BigUint {
+    let p = &self.p;
+    let x_cubed = x.mul_mod(x, p).mul_mod(x, p);
+    x_cubed
+      .add_mod(&self.a.mul_mod(x, p), p)
+      .add_mod(&self.b, p)
+  }
+
+  pub fn contains(&self, point: &Point) -> bool {
+    match *point {
+      Point::Infinity => true,
+      Point::Affine(ref x, ref y) => y.mul_mod(y, &self.p) == self.rhs(x),
+    }
+  }
+
+  pub fn negate(&self, point: &Point) -> Point {
+    match *point {
+      Point::Infinity => Point::Infinity,
+      Point::Affine(ref x, ref y) => Point::Affine(x.clone(), BigUint::zero().sub_mod(y, &self.p)),
+    }
+  }
+
+  pub fn add(&self, p1: &Point, p2: &Point) -> Point {
+    let p = &self.p;
+    let (x1, y1, x2, y2) = match (p1, p2) {
+      (&Point::Infinity, _) => return p2.clone(),
+      (_, &Point::Infinity) => return p1.clone(),
+      (Point::Affine(x1, y1), Point::Affine(x2, y2)) => (x1, y1, x2, y2),
+    };
+    if x1 == x2 && y1.add_mod(y2, p).is_zero() {
+      return Point::Infinity;
+    }
+    let slope = if x1 == x2 {
+      let numerator = BigUint::from_u64(3)
+        .mul_mod(&x1.mul_mod(x1, p), p)
+        .add_mod(&self.a, p);
+      let denominator = BigUint::from_u64(2).mul_mod(y1, p);
+      numerator.mul_mod(&denominator.modinv(p).unwrap(), p)
+    } else {
+      let numerator = y2.sub_mod(y1, p);
+      let denominator = x2.sub_mod(x1, p);
+      numerator.mul_mod(&denominator.modinv(p).unwrap(), p)
+    };
+    let x3 = slope.mul_mod(&slope, p).sub_mod(x1, p).sub_mod(x2, p);
+    let y3 = slope.mul_mod(&x1.sub_mod(&x3, p), p).sub_mod(y1, p);
+    Point::Affine(x3, y3)
+  }
+
+  pub fn double(&self, point: &Point) -> Point {
+    self.add(point, point)
+  }
+
+  pub fn scalar_mul(&self, point: &Point, k: &BigUint) -> Point {
+    let mut result = Point::Infinity;
+    for i in (0..k.bits()).rev() {
+      result = self.double(&result);
+      if k.bit(i) {
+        result = self.add(&result, point);
+      }
+    }
+    result
+  }
+
+  // One of the two points with the given x coordinate, if there are any.
+  pub fn point_with_x(&self, x: &BigUint) -> Option<Point> {
+    self
+      .rhs(x)
+      .mod_sqrt(&self.p)
+      .map(|y| Point::Affine(x.clone(), y))
+  }
+
+  pub fn random_point<R: Rng>(&self, rng: &mut R) -> Point {
+    loop {
+      let x = BigUint::random_below(rng, &self.p);
+      if let Some(point) = self.point_with_x(&x) {
+        return point;
+      }
+    }
+  }
+}
+
+impl dlog::Group for WeierstrassCurve {
+  type Element = Point;
+
+  fn identity(&self) -> Point {
+    Point::Infinity
+  }
+
+  fn combine(&self, a: &Point, b: &Point) -> Point {
+    self.add(a, b)
+  }
+
+  fn inverse(&self, point: &Point) -> Point {
+    self.negate(point)
+  }
+
+  fn power(&self, point: &Point, k: &BigUint) -> Point {
+    self.scalar_mul(point, k)
+  }
+
+  fn random_element<R: Rng>(&self, rng: &mut R) -> Point {
+    self.random_point(rng)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+  pub r: BigUint,
+  pub s: BigUint,
+}
+
+// A curve together with a generator of a subgroup of prime order.
+#[derive(Clone, Debug)]
+pub struct Domain {
+  pub curve: WeierstrassCurve,
+  pub generator: Point,
+  pub order: BigUint,
+}
+
+impl Domain {
+  // y^2 = x^3 - 95051x + 11279326 from cryptopals set 8. The curve has order
+  // 8 * order.
+  pub fn cryptopals() -> Domain {
+    let p = BigUint::from_str_radix("233970423115425145524320034830162017933", 10);
+    let a = BigUint::zero().sub_mod(&BigUint::from_u64(95051), &p);
+    Domain {
+      curve: WeierstrassCurve::new(a, BigUint::from_u64(11279326), p),
+      generator: Point::Affine(
+        BigUint::from_u64(182),
+        BigUint::from_str_radix("85518893674295321206118380980485522083", 10),
+      ),
+      order: BigUint::from_str_radix("29246302889428143187362802287225875743", 10),
+    }
+  }
+
+  pub fn generate_keypair<R: Rng>(&self, rng: &mut R) -> (BigUint, Point) {
+    let private_key = self.random_scalar(rng);
+    let public_key = self.curve.scalar_mul(&self.generator, &private_key);
+    (private_key, public_key)
+  }
+
+  pub fn shared_secret(&self, private_key: &BigUint, public_key: &Point) -> Point {
+    self.curve.scalar_mul(public_key, private_key)
+  }
+
+  fn random_scalar<R: Rng>(&self, rng: &mut R) -> BigUint {
+    loop {
+      let k = BigUint::random_below(rng, &self.order);
+      if !k.is_zero() {
+        return k;
+      }
+    }
+  }
+
+  // The leftmost bits of the SHA-1 digest, as many as the order has.
+  fn hash_message(&self, message: &[u8]) -> BigUint {
+    let digest = BigUint::from_bytes_be(&sha1::hash(message));
+    let digest_bits = sha1::OUTPUT_SIZE * 8;
+    &digest >> digest_bits.saturating_sub(self.order.bits())
+  }
+
+  pub fn sign<R: Rng>(&self, rng: &mut R, private_key: &BigUint, message: &[u8]) -> Signature {
+    let n = &self.order;
+    let z = self.hash_message(message);
+    loop {
+      let k = self.random_scalar(rng);
+      let r = match self.curve.scalar_mul(&self.generator, &k) {
+        Point::Affine(x, _) => &x % n,
+        Point::Infinity => continue,
+      };
+      if r.is_zero() {
+        continue;
+      }
+      let s = k
+        .modinv(n)
+        .unwrap()
+        .mul_mod(&z.add_mod(&r.mul_mod(private_key, n), n), n);
+      if !s.is_zero() {
+        return Signature { r, s };
+      }
+    }
+  }
+
+  pub fn verify(&self, public_key: &Point, message: &[u8], signature: &Signature) -> bool {
+    let n = &self.order;
+    let Signature { ref r, ref s } = *signature;
+    if r.is_zero() || r >= n || s.is_zero() || s >= n {
+      return false;
+    }
+    let w = s.modinv(n).unwrap();
+    let u1 = self.hash_message(message).mul_mod(&w, n);
+    let u2 = r.mul_mod(&w, n);
+    let point = self.curve.add(
+      &self.curve.scalar_mul(&self.generator, &u1),
+      &self.curve.scalar_mul(public_key, &u2),
+    );
+    match point {
+      Point::Affine(x, _) => &x % n == *r,
+      Point::Infinity => false,
+    }
+  }
+}
+
+// Bv^2 = u^3 + Au^2 + u over the integers modulo the prime p, used through
+// its u coordinate only.
+#[derive(Clone, Debug)]
+pub struct MontgomeryCurve {
+  pub a: BigUint,
+  pub b: BigUint,
+  pub p: BigUint,
+}
+
+impl MontgomeryCurve {
+  pub fn new(a: BigUint, b: BigUint, p: BigUint) -> MontgomeryCurve {
+    MontgomeryCurve { a, b, p }
+  }
+
+  // v^2 = u^3 + 534u^2 + u, birationally equivalent to the curve of
+  // Domain::cryptopals() via u = x - 178. The base point is u = 4.
+  pub fn cryptopals() -> MontgomeryCurve {
+    MontgomeryCurve::new(
+      BigUint::from_u64(534),
+      BigUint::one(),
+      BigUint::from_str_radix("233970423115425145524320034830162017933", 10),
+    )
+  }
+
+  // v^2 for the given u.
+  fn v_squared(&self, u: &BigUint) -> BigUint {
+    let p = &self.p;
+    let rhs = u
+      .mul_mod(u, p)
+      .mul_mod(u, p)
+      .add_mod(&self.a.mul_mod(&u.mul_mod(u, p), p), p)
+      .add_mod(u, p);
+    rhs.mul_mod(&self.b.modinv(p).unwrap(), p)
+  }
+
+  // Points whose u coordinate isn't on the curve are on its quadratic twist.
+  pub fn is_on_curve(&self, u: &BigUint) -> bool {
+    self.v_squared(u).mod_sqrt(&self.p).is_some()
+  }
+
+  // Computes the u coordinate of k * (u, v) without ever needing v. The
+  // identity maps to 0.
+  pub fn ladder(&self, u: &BigUint, k: &BigUint) -> BigUint {
+    let p = &self.p;
+    let four = BigUint::from_u64(4);
+    let (mut u2, mut w2) = (BigUint::one(), BigUint::zero());
+    let (mut u3, mut w3) = (u % p, BigUint::one());
+    for i in (0..p.bits()).rev() {
+      if k.bit(i) {
+        ::std::mem::swap(&mut u2, &mut u3);
+        ::std::mem::swap(&mut w2, &mut w3);
+      }
+      let new_u3 = {
+        let t = u2.mul_mod(&u3, p).sub_mod(&w2.mul_mod(&w3, p), p);
+        t.mul_mod(&t, p)
+      };
+      let new_w3 = {
+        let t = u2.mul_mod(&w3, p).sub_mod(&w2.mul_mod(&u3, p), p);
+        u.mul_mod(&t.mul_mod(&t, p), p)
+      };
+      let u2_squared = u2.mul_mod(&u2, p);
+      let w2_squared = w2.mul_mod(&w2, p);
+      let new_u2 = {
+        let t = u2_squared.sub_mod(&w2_squared, p);
+        t.mul_mod(&t, p)
+      };
+      let new_w2 = four.mul_mod(&u2.mul_mod(&w2, p), p).mul_mod(
+        &u2_squared
+          .add_mod(&self.a.mul_mod(&u2.mul_mod(&w2, p), p), p)
+          .add_mod(&w2_squared, p),
+        p,
+      );
+      u2 = new_u2;
+      w2 = new_w2;
+      u3 = new_u3;
+      w3 = new_w3;
+      if k.bit(i) {
+        ::std::mem::swap(&mut u2, &mut u3);
+        ::std::mem::swap(&mut w2, &mut w3);
+      }
+    }
+    match w2.modinv(p) {
+      Some(inverse) => u2.mul_mod(&inverse, p),
+      None => BigUint::zero(),
+    }
+  }
+}
+
+#[test]
+fn test_generator_order() {
+  let domain = Domain::cryptopals();
+  assert!(domain.curve.contains(&domain.generator));
+  assert_eq!(
+    Point::Infinity,
+    domain.curve.scalar_mul(&domain.generator, &domain.order)
+  );
+}
+
+#[test]
+fn test_group_law() {
+  use rand::thread_rng;
+  let domain = Domain::cryptopals();
+  let curve = &domain.curve;
+  let mut rng = thread_rng();
+  let p1 = curve.random_point(&mut rng);
+  let p2 = curve.random_point(&mut rng);
+  let p3 = curve.random_point(&mut rng);
+  assert!(curve.contains(&p1));
+  assert!(curve.contains(&curve.add(&p1, &p2)));
+  assert_eq!(curve.add(&p1, &p2), curve.add(&p2, &p1));
+  assert_eq!(
+    curve.add(&curve.add(&p1, &p2), &p3),
+    curve.add(&p1, &curve.add(&p2, &p3))
+  );
+  assert_eq!(Point::Infinity, curve.add(&p1, &curve.negate(&p1)));
+  assert_eq!(
+    curve.add(&p1, &curve.double(&p1)),
+    curve.scalar_mul(&p1, &BigUint::from_u64(3))
+  );
+}
+
+#[test]
+fn test_ecdh() {
+  use rand::thread_rng;
+  let domain = Domain::cryptopals();
+  let mut rng = thread_rng();
+  let (alice_private, alice_public) = domain.generate_keypair(&mut rng);
+  let (bob_private, bob_public) = domain.generate_keypair(&mut rng);
+  assert_eq!(
+    domain.shared_secret(&alice_private, &bob_public),
+    domain.shared_secret(&bob_private, &alice_public)
+  );
+}
+
+#[test]
+fn test_ecdsa() {
+  use rand::thread_rng;
+  let domain = Domain::cryptopals();
+  let mut rng = thread_rng();
+  let (private_key, public_key) = domain.generate_keypair(&mut rng);
+  let signature = domain.sign(&mut rng, &private_key, b"hello world");
+  assert!(domain.verify(&public_key, b"hello world", &signature));
+  assert!(!domain.verify(&public_key, b"hello world!", &signature));
+  let (_, other_public_key) = domain.generate_keypair(&mut rng);
+  assert!(!domain.verify(&other_public_key, b"hello world", &signature));
+}
+
+#[test]
+fn test_ladder() {
+  use rand::thread_rng;
+  let curve = MontgomeryCurve::cryptopals();
+  let domain = Domain::cryptopals();
+  let base = BigUint::from_u64(4);
+  assert!(curve.is_on_curve(&base));
+  assert!(curve.ladder(&base, &domain.order).is_zero());
+  // The ladder agrees with the Weierstrass form under u = x - 178
+  let k = BigUint::random_below(&mut thread_rng(), &domain.order);
+  match domain.curve.scalar_mul(&domain.generator, &k) {
+    Point::Affine(x, _) => assert_eq!(
+      x.sub_mod(&BigUint::from_u64(178), &curve.p),
+      curve.ladder(&base, &k)
+    ),
+    Point::Infinity => unreachable!(),
+  }
+}
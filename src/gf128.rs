@@ -0,0 +1,171 @@
+use rand::Rng;
+use std::ops::{Add, AddAssign, Mul, MulAssign};
+
+// An element of GF(2^128) using the GCM representation: the most significant
+// bit of the first byte is the coefficient of x^0 and the field is defined by
+// x^128 + x^7 + x^2 + x + 1. Since the field has characteristic 2 addition
+// and subtraction are both XOR.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Element(u128);
+
+const R: u128 = 0xe1 << 120;
+
+impl Element {
+  pub fn zero() -> Element {
+    Element(0)
+  }
+
+  pub fn one() -> Element {
+    Element(1 << 127)
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Element {
+    assert!(bytes.len() == 16, "Expected 16 bytes, got {}", bytes.len());
+    Element(
+      bytes
+        .iter()
+        .fold(0u128, |accum, &byte| (accum << 8) | u128::from(byte)),
+    )
+  }
+
+  pub fn to_bytes(self) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+      *byte = (self.0 >> (8 * (15 - i))) as u8;
+    }
+    bytes
+  }
+
+  pub fn random<R: Rng>(rng: &mut R) -> Element {
+    let mut bytes = [0; 16];
+    rng.fill(&mut bytes);
+    Element::from_bytes(&bytes)
+  }
+
+  pub fn is_zero(self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn pow(self, mut exponent: u128) -> Element {
+    let mut base = self;
+    let mut result = Element::one();
+    while exponent > 0 {
+      if exponent & 1 == 1 {
+        result *= base;
+      }
+      base *= base;
+      exponent >>= 1;
+    }
+    result
+  }
+
+  pub fn inverse(self) -> Element {
+    assert!(!self.is_zero(), "Zero has no inverse");
+    // The multiplicative group has order 2^128 - 1
+    self.pow(u128::MAX - 1)
+  }
+
+  // Every element has exactly one square root: a^(2^127).
+  pub fn sqrt(self) -> Element {
+    let mut result = self;
+    for _ in 0..127 {
+      result *= result;
+    }
+    result
+  }
+}
+
+impl Add for Element {
+  type Output = Element;
+  // Addition in a field of characteristic two is xor.
+  #[allow(clippy::suspicious_arithmetic_impl)]
+  fn add(self, other: Element) -> Element {
+    Element(self.0 ^ other.0)
+  }
+}
+
+impl AddAssign for Element {
+  #[allow(clippy::suspicious_op_assign_impl)]
+  fn add_assign(&mut self, other: Element) {
+    self.0 ^= other.0;
+  }
+}
+
+impl Mul for Element {
+  type Output = Element;
+  fn mul(self, other: Element) -> Element {
+    let mut z = 0u128;
+    let mut v = other.0;
+    for i in 0..128 {
+      if (self.0 >> (127 - i)) & 1 == 1 {
+        z ^= v;
+      }
+      v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    Element(z)
+  }
+}
+
+impl MulAssign for Element {
+  fn mul_assign(&mut self, other: Element) {
+    *self = *self * other;
+  }
+}
+
+#[test]
+fn test_bytes_round_trip() {
+  use util::parse_byte_string;
+  let bytes = parse_byte_string("66e94bd4ef8a2c3b884cfa59ca342b2e");
+  assert_eq!(&bytes[..], &Element::from_bytes(&bytes).to_bytes()[..]);
+  assert_eq!(0x80, Element::one().to_bytes()[0]);
+}
+
+#[test]
+fn test_mul() {
+  use util::parse_byte_string;
+  // x * x^127 = x^128 = x^7 + x^2 + x + 1
+  let x = Element::from_bytes(&parse_byte_string("40000000000000000000000000000000"));
+  let x_127 = Element::from_bytes(&parse_byte_string("00000000000000000000000000000001"));
+  assert_eq!(
+    Element::from_bytes(&parse_byte_string("e1000000000000000000000000000000")),
+    x * x_127
+  );
+  assert_eq!(x, x * Element::one());
+  assert_eq!(Element::zero(), x * Element::zero());
+}
+
+#[cfg(test)]
+mod qctests {
+  use super::Element;
+  use quickcheck::{Arbitrary, Gen, TestResult};
+
+  impl Arbitrary for Element {
+    fn arbitrary<G: Gen>(g: &mut G) -> Element {
+      let high: u64 = g.gen();
+      let low: u64 = g.gen();
+      Element((u128::from(high) << 64) | u128::from(low))
+    }
+  }
+
+  quickcheck! {
+    fn inverse(a: Element) -> TestResult {
+      if a.is_zero() {
+        return TestResult::discard();
+      }
+      TestResult::from_bool(a * a.inverse() == Element::one())
+    }
+
+    fn distributive(a: Element, b: Element, c: Element) -> bool {
+      a * (b + c) == a * b + a * c
+    }
+
+    fn commutative(a: Element, b: Element) -> bool {
+      a * b == b * a
+    }
+
+    fn sqrt(a: Element) -> bool {
+      let root = a.sqrt();
+      root * root == a
+    }
+  }
+}
@@ -0,0 +1,44 @@
+use sha1;
+
+pub fn hmac(
+  hash: &dyn Fn(&[u8]) -> Vec<u8>,
+  block_size: usize,
+  key: &[u8],
+  message: &[u8],
+) -> Vec<u8> {
+  let mut block_key = if key.len() > block_size {
+    hash(key)
+  } else {
+    key.to_vec()
+  };
+  block_key.resize(block_size, 0);
+  let mut inner = block_key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>();
+  inner.extend_from_slice(message);
+  let mut outer = block_key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>();
+  outer.extend(hash(&inner));
+  hash(&outer)
+}
+
+pub fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+  hmac(&sha1::hash, sha1::BLOCK_SIZE, key, message)
+}
+
+#[test]
+fn rfc2202_test_vectors() {
+  use util::parse_byte_string;
+  assert_eq!(
+    parse_byte_string("b617318655057264e28bc0b6fb378c8ef146be00"),
+    hmac_sha1(&[0x0b; 20], b"Hi There")
+  );
+  assert_eq!(
+    parse_byte_string("effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"),
+    hmac_sha1(b"Jefe", b"what do ya want for nothing?")
+  );
+  assert_eq!(
+    parse_byte_string("aa4ae5e15272d00e95705637ce8a3b55ed402112"),
+    hmac_sha1(
+      &[0xaa; 80],
+      b"Test Using Larger Than Block-Size Key - Hash Key First"
+    )
+  );
+}
@@ -5,13 +5,40 @@ extern crate quickcheck;
 extern crate rand;
 extern crate rayon;
 
+pub mod aead;
 pub mod aes;
 pub mod analysis;
 pub mod b64;
+#[cfg(test)]
+mod beast;
+pub mod bigint;
+pub mod cmac;
+pub mod dfa;
+pub mod dlog;
+pub mod ec;
+pub mod gf128;
+pub mod hmac;
+#[cfg(test)]
+mod lucky13;
+pub mod md;
+pub mod modes;
 pub mod oracle;
+pub mod padding;
 pub mod pkcs7;
+pub mod poly;
+#[cfg(test)]
+mod poodle;
+pub mod rc4;
+pub mod sha1;
+pub mod spn;
+pub mod square;
+#[cfg(test)]
+mod sweet32;
+pub mod toy32;
 pub mod util;
 pub mod xor;
+pub mod xtea;
+pub mod xts;
 
 mod s1c1;
 mod s1c2;
@@ -30,3 +57,18 @@ mod s2c14;
 mod s2c15;
 mod s2c16;
 mod s2c9;
+
+#[cfg(test)]
+mod s3c17;
+
+mod s7c52;
+mod s7c53;
+mod s7c54;
+#[cfg(test)]
+mod s7c56;
+
+mod s8c57;
+mod s8c58;
+mod s8c59;
+mod s8c60;
+mod s8c63;
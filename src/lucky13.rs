@@ -0,0 +1,104 @@
+use oracle::TlsRecord;
+use rayon::prelude::*;
+
+// Sends the target block after a forged previous block, behind two blocks of
+// filler, and returns the cost of the rejection. With four blocks after the
+// IV the MAC covers 13 + 64 - 20 = 57 bytes when the padding is bad or a
+// single byte, which takes one more compression in the inner hash than the
+// 55 bytes or fewer left after two or more bytes of valid padding.
+fn cost(o: &TlsRecord, previous: &[u8], block: &[u8]) -> usize {
+  let mut record = vec![0; 48];
+  record.extend_from_slice(previous);
+  record.extend_from_slice(block);
+  o.open_timed(&record).1
+}
+
+fn recover_block(o: &TlsRecord, previous: &[u8], block: &[u8]) -> Vec<u8> {
+  let mut plaintext = vec![0; 16];
+
+  // One byte of padding is as slow as bad padding, so the last two bytes
+  // have to be guessed together until they decrypt to 02 02. A guess that
+  // only works because of longer padding stops working once the byte before
+  // them is changed.
+  let is_fast = |guess: usize, slow: usize, tweak: u8| {
+    let mut modified = previous.to_vec();
+    modified[13] ^= tweak;
+    modified[14] ^= (guess >> 8) as u8;
+    modified[15] ^= guess as u8;
+    cost(o, &modified, block) < slow
+  };
+  let costs = (0..0x10000)
+    .into_par_iter()
+    .map(|guess| {
+      let mut modified = previous.to_vec();
+      modified[14] ^= (guess >> 8) as u8;
+      modified[15] ^= guess as u8;
+      cost(o, &modified, block)
+    })
+    .collect::<Vec<usize>>();
+  let slow = *costs.iter().max().unwrap();
+  let guess = (0..0x10000)
+    .find(|&guess| costs[guess] < slow && is_fast(guess, slow, 1))
+    .unwrap();
+  plaintext[14] = (guess >> 8) as u8 ^ 2;
+  plaintext[15] = guess as u8 ^ 2;
+
+  // From there on, padding one byte longer than what's known is only valid
+  // for the right guess of the byte before it.
+  for i in (0..14).rev() {
+    let padding = (16 - i) as u8;
+    let mut modified = previous.to_vec();
+    for j in i + 1..16 {
+      modified[j] ^= plaintext[j] ^ padding;
+    }
+    let costs = (0..=255u8)
+      .map(|guess| {
+        modified[i] = previous[i] ^ guess;
+        cost(o, &modified, block)
+      })
+      .collect::<Vec<usize>>();
+    let fast = *costs.iter().min().unwrap();
+    assert!(fast < *costs.iter().max().unwrap());
+    let guess = costs.iter().position(|&cost| cost == fast).unwrap();
+    plaintext[i] = guess as u8 ^ padding;
+  }
+  plaintext
+}
+
+#[test]
+fn record_costs() {
+  use aead;
+  let o = TlsRecord::new();
+  // This fills four blocks with a single byte of padding.
+  let message = b"GET /index.html HTTP/1.1\r\nHost: example.com".to_vec();
+  assert_eq!(63, message.len() + aead::TAG_SIZE);
+  let record = o.seal(&message);
+  assert_eq!(Ok(message), o.open(&record));
+
+  // Every forged record is rejected the same way. Each one turns the
+  // padding byte into a 2 and changes the byte before it, and only the
+  // change that makes that a 2 as well saves a compression.
+  let mut costs = (0..=255u8)
+    .map(|guess| {
+      let mut modified = record.clone();
+      modified[62] ^= guess;
+      modified[63] ^= 1 ^ 2;
+      let (result, cost) = o.open_timed(&modified);
+      assert_eq!(Err(aead::Error::BadMac), result);
+      cost
+    })
+    .collect::<Vec<usize>>();
+  costs.sort();
+  costs.dedup();
+  assert_eq!(2, costs.len());
+}
+
+#[test]
+fn lucky_thirteen() {
+  let o = TlsRecord::new();
+  let message = b"Cookie: session=7f3ac91e25bd0648; user=alice";
+  let record = o.seal(message);
+  // The block after "Cookie: session=" is all secret.
+  let recovered = recover_block(&o, &record[16..32], &record[32..48]);
+  assert_eq!(message[16..32], recovered[..]);
+}
@@ -0,0 +1,388 @@
+use aes;
+use rand::prelude::*;
+use std::collections::HashMap;
+use util;
+
+pub const BLOCK_SIZE: usize = 16;
+
+// A deliberately weak Merkle-Damgård hash. The compression function encrypts
+// each message block under the chaining state (zero padded to an AES-128 key)
+// and truncates the result back down to the state size.
+#[derive(Clone)]
+pub struct MDHash {
+  initial_state: Vec<u8>,
+}
+
+impl MDHash {
+  pub fn new(state_size: usize) -> MDHash {
+    MDHash::with_initial_state(vec![0; state_size])
+  }
+
+  pub fn with_initial_state(initial_state: Vec<u8>) -> MDHash {
+    assert!(
+      !initial_state.is_empty() && initial_state.len() <= 16,
+      "Unsupported state size {}",
+      initial_state.len()
+    );
+    MDHash { initial_state }
+  }
+
+  pub fn state_size(&self) -> usize {
+    self.initial_state.len()
+  }
+
+  pub fn initial_state(&self) -> &[u8] {
+    &self.initial_state
+  }
+
+  pub fn compress(&self, state: &[u8], block: &[u8]) -> Vec<u8> {
+    assert_eq!(self.state_size(), state.len());
+    assert_eq!(BLOCK_SIZE, block.len());
+    let mut key = state.to_vec();
+    key.resize(16, 0);
+    let output = aes::Cipher::new(&key).encrypt_block(&util::convert_to_fixed_array(block));
+    output[..self.state_size()].to_vec()
+  }
+
+  // Runs the compression function over whole blocks without any padding.
+  pub fn hash_blocks(&self, state: &[u8], blocks: &[u8]) -> Vec<u8> {
+    assert_eq!(0, blocks.len() % BLOCK_SIZE);
+    blocks
+      .chunks(BLOCK_SIZE)
+      .fold(state.to_vec(), |state, block| self.compress(&state, block))
+  }
+
+  pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+    self.hash_blocks(&self.initial_state, &pad(data))
+  }
+
+  pub fn find_collision(&self, state: &[u8]) -> Collision {
+    self.find_collision_between(state, state)
+  }
+
+  // Finds blocks a and b such that compress(state_a, a) == compress(state_b, b)
+  // by growing a table of outputs for each side until they meet.
+  pub fn find_collision_between(&self, state_a: &[u8], state_b: &[u8]) -> Collision {
+    let mut rng = thread_rng();
+    let mut outputs_a = HashMap::new();
+    let mut outputs_b = HashMap::new();
+    loop {
+      let block_a = util::gen_random_bytes(&mut rng, BLOCK_SIZE);
+      let output_a = self.compress(state_a, &block_a);
+      if let Some(block_b) = outputs_b.get(&output_a) {
+        if *block_b != block_a || state_a != state_b {
+          return Collision {
+            block_a,
+            block_b: Vec::clone(block_b),
+            state: output_a,
+          };
+        }
+      }
+      outputs_a.insert(output_a, block_a);
+
+      let block_b = util::gen_random_bytes(&mut rng, BLOCK_SIZE);
+      let output_b = self.compress(state_b, &block_b);
+      if let Some(block_a) = outputs_a.get(&output_b) {
+        if *block_a != block_b || state_a != state_b {
+          return Collision {
+            block_a: Vec::clone(block_a),
+            block_b,
+            state: output_b,
+          };
+        }
+      }
+      outputs_b.insert(output_b, block_b);
+    }
+  }
+
+  // Joux's construction: chaining n single block collisions yields 2^n
+  // messages of n blocks that all share the same hash.
+  pub fn multicollision(&self, state: &[u8], n: usize) -> Multicollision {
+    let mut multicollision = Multicollision {
+      collisions: Vec::with_capacity(n),
+      initial_state: state.to_vec(),
+    };
+    for _ in 0..n {
+      multicollision.extend(self);
+    }
+    multicollision
+  }
+}
+
+// Merkle-Damgård strengthening: a 1 bit, zeros and then the message length in
+// bits as a big endian 64 bit integer.
+pub fn pad(data: &[u8]) -> Vec<u8> {
+  let mut padded = data.to_vec();
+  padded.push(0x80);
+  while padded.len() % BLOCK_SIZE != BLOCK_SIZE - 8 {
+    padded.push(0);
+  }
+  let bit_length = (data.len() as u64) * 8;
+  for i in (0..8).rev() {
+    padded.push((bit_length >> (8 * i)) as u8);
+  }
+  padded
+}
+
+#[test]
+fn pad_lengths() {
+  assert_eq!(16, pad(&[]).len());
+  assert_eq!(16, pad(&[0; 7]).len());
+  assert_eq!(32, pad(&[0; 8]).len());
+  assert_eq!(32, pad(&[0; 16]).len());
+  assert_eq!(
+    &[0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x18][..],
+    &pad(&[0; 3])[3..]
+  );
+}
+
+#[test]
+fn hash_is_deterministic() {
+  let h = MDHash::new(2);
+  assert_eq!(h.hash(b"YELLOW SUBMARINE"), h.hash(b"YELLOW SUBMARINE"));
+  assert_ne!(h.hash(b"YELLOW SUBMARINE"), h.hash(b"YELLOW SUBMARINF"));
+  assert_eq!(2, h.hash(b"").len());
+}
+
+#[derive(Clone, Debug)]
+pub struct Collision {
+  pub block_a: Vec<u8>,
+  pub block_b: Vec<u8>,
+  pub state: Vec<u8>,
+}
+
+#[test]
+fn test_find_collision() {
+  let h = MDHash::new(2);
+  let collision = h.find_collision(h.initial_state());
+  assert_ne!(collision.block_a, collision.block_b);
+  assert_eq!(
+    collision.state,
+    h.compress(h.initial_state(), &collision.block_a)
+  );
+  assert_eq!(
+    collision.state,
+    h.compress(h.initial_state(), &collision.block_b)
+  );
+}
+
+pub struct Multicollision {
+  collisions: Vec<Collision>,
+  initial_state: Vec<u8>,
+}
+
+impl Multicollision {
+  // Doubles the number of colliding messages.
+  pub fn extend(&mut self, hash: &MDHash) {
+    let collision = hash.find_collision(&self.state());
+    self.collisions.push(collision);
+  }
+
+  pub fn state(&self) -> Vec<u8> {
+    self
+      .collisions
+      .last()
+      .map(|c| c.state.clone())
+      .unwrap_or_else(|| self.initial_state.clone())
+  }
+
+  pub fn message_count(&self) -> usize {
+    1 << self.collisions.len()
+  }
+
+  pub fn collisions(&self) -> &[Collision] {
+    &self.collisions
+  }
+
+  // Bit i of index selects which block of the i-th collision is used.
+  pub fn message(&self, index: usize) -> Vec<u8> {
+    assert!(index < self.message_count());
+    let mut message = Vec::with_capacity(self.collisions.len() * BLOCK_SIZE);
+    for (i, collision) in self.collisions.iter().enumerate() {
+      if index & (1 << i) == 0 {
+        message.extend(&collision.block_a);
+      } else {
+        message.extend(&collision.block_b);
+      }
+    }
+    message
+  }
+
+  pub fn messages<'a>(&'a self) -> impl Iterator<Item = Vec<u8>> + 'a {
+    (0..self.message_count()).map(move |i| self.message(i))
+  }
+}
+
+#[test]
+fn test_multicollision() {
+  let h = MDHash::new(2);
+  let multicollision = h.multicollision(h.initial_state(), 3);
+  assert_eq!(8, multicollision.message_count());
+  let mut messages: Vec<Vec<u8>> = multicollision.messages().collect();
+  let expected_hash = h.hash(&messages[0]);
+  for message in messages.iter() {
+    assert_eq!(expected_hash, h.hash(message));
+  }
+  messages.sort();
+  messages.dedup();
+  assert_eq!(8, messages.len());
+}
+
+// Kelsey and Schneier's expandable message: k collisions between a single
+// block and 2^i + 1 blocks which together can produce a message of any length
+// between k and k + 2^k - 1 blocks that hashes to the same state.
+pub struct ExpandableMessage {
+  // (short block, long blocks) pairs, longest first
+  pieces: Vec<(Vec<u8>, Vec<u8>)>,
+  state: Vec<u8>,
+}
+
+impl ExpandableMessage {
+  pub fn new(hash: &MDHash, state: &[u8], k: usize) -> ExpandableMessage {
+    let dummy_block = [0; BLOCK_SIZE];
+    let mut state = state.to_vec();
+    let mut pieces = Vec::with_capacity(k);
+    for i in (0..k).rev() {
+      let mut prefix = Vec::with_capacity((1 << i) * BLOCK_SIZE);
+      let mut prefix_state = state.clone();
+      for _ in 0..(1 << i) {
+        prefix.extend_from_slice(&dummy_block);
+        prefix_state = hash.compress(&prefix_state, &dummy_block);
+      }
+      let collision = hash.find_collision_between(&state, &prefix_state);
+      prefix.extend(&collision.block_b);
+      pieces.push((collision.block_a, prefix));
+      state = collision.state;
+    }
+    ExpandableMessage { pieces, state }
+  }
+
+  pub fn state(&self) -> &[u8] {
+    &self.state
+  }
+
+  pub fn min_blocks(&self) -> usize {
+    self.pieces.len()
+  }
+
+  pub fn max_blocks(&self) -> usize {
+    self.pieces.len() + (1 << self.pieces.len()) - 1
+  }
+
+  pub fn message(&self, blocks: usize) -> Vec<u8> {
+    assert!(
+      blocks >= self.min_blocks() && blocks <= self.max_blocks(),
+      "Cannot produce a message of {} blocks",
+      blocks
+    );
+    let extra_blocks = blocks - self.min_blocks();
+    let k = self.pieces.len();
+    let mut message = Vec::with_capacity(blocks * BLOCK_SIZE);
+    for (i, (short, long)) in self.pieces.iter().enumerate() {
+      if extra_blocks & (1 << (k - 1 - i)) == 0 {
+        message.extend(short);
+      } else {
+        message.extend(long);
+      }
+    }
+    message
+  }
+}
+
+#[test]
+fn test_expandable_message() {
+  let h = MDHash::new(2);
+  let expandable = ExpandableMessage::new(&h, h.initial_state(), 4);
+  assert_eq!(4, expandable.min_blocks());
+  assert_eq!(19, expandable.max_blocks());
+  for blocks in expandable.min_blocks()..expandable.max_blocks() + 1 {
+    let message = expandable.message(blocks);
+    assert_eq!(blocks * BLOCK_SIZE, message.len());
+    assert_eq!(
+      expandable.state(),
+      &h.hash_blocks(h.initial_state(), &message)[..]
+    );
+  }
+}
+
+// The "diamond structure" used for herding: 2^k leaf states which are paired
+// up by collisions level by level until a single root state remains.
+pub struct DiamondStructure {
+  // Each level holds the colliding blocks that lead from the previous level's
+  // states (starting with the leaves) to the next, half as many, states.
+  leaves: Vec<Vec<u8>>,
+  levels: Vec<Vec<Collision>>,
+}
+
+impl DiamondStructure {
+  pub fn new(hash: &MDHash, k: usize) -> DiamondStructure {
+    // With k = 0 there is a single leaf and no root to herd towards.
+    assert!(k > 0, "A diamond structure needs k of at least 1");
+    let mut rng = thread_rng();
+    let mut leaves: Vec<Vec<u8>> = Vec::with_capacity(1 << k);
+    while leaves.len() < (1 << k) {
+      let state = util::gen_random_bytes(&mut rng, hash.state_size());
+      if !leaves.contains(&state) {
+        leaves.push(state);
+      }
+    }
+    let mut levels = Vec::with_capacity(k);
+    let mut states = leaves.clone();
+    while states.len() > 1 {
+      let level: Vec<Collision> = states
+        .chunks(2)
+        .map(|pair| hash.find_collision_between(&pair[0], &pair[1]))
+        .collect();
+      states = level.iter().map(|c| c.state.clone()).collect();
+      levels.push(level);
+    }
+    DiamondStructure { leaves, levels }
+  }
+
+  pub fn leaves(&self) -> &[Vec<u8>] {
+    &self.leaves
+  }
+
+  pub fn state(&self) -> &[u8] {
+    &self.levels.last().unwrap()[0].state
+  }
+
+  // Number of blocks needed to get from any leaf to the root.
+  pub fn depth(&self) -> usize {
+    self.levels.len()
+  }
+
+  pub fn leaf_index(&self, state: &[u8]) -> Option<usize> {
+    self.leaves.iter().position(|leaf| leaf[..] == state[..])
+  }
+
+  pub fn path_from(&self, leaf_index: usize) -> Vec<u8> {
+    let mut index = leaf_index;
+    let mut path = Vec::with_capacity(self.depth() * BLOCK_SIZE);
+    for level in self.levels.iter() {
+      let collision = &level[index / 2];
+      if index & 1 == 0 {
+        path.extend(&collision.block_a);
+      } else {
+        path.extend(&collision.block_b);
+      }
+      index /= 2;
+    }
+    path
+  }
+}
+
+#[test]
+fn test_diamond_structure() {
+  let h = MDHash::new(2);
+  let diamond = DiamondStructure::new(&h, 3);
+  assert_eq!(8, diamond.leaves().len());
+  assert_eq!(3, diamond.depth());
+  for (i, leaf) in diamond.leaves().iter().enumerate() {
+    assert_eq!(Some(i), diamond.leaf_index(leaf));
+    assert_eq!(
+      diamond.state(),
+      &h.hash_blocks(leaf, &diamond.path_from(i))[..]
+    );
+  }
+}
@@ -0,0 +1,565 @@
+use aes::{self, Operation};
+use rayon::prelude::*;
+use xor;
+
+// A keyed permutation on blocks of BLOCK_SIZE bytes, transforming them in
+// place.
+pub trait BlockCipher: Sync {
+  const BLOCK_SIZE: usize;
+
+  fn encrypt(&self, block: &mut [u8]);
+  fn decrypt(&self, block: &mut [u8]);
+
+  // Messages at least this long are split across threads in the modes where
+  // blocks are independent of each other.
+  fn parallel_threshold(&self) -> usize {
+    aes::DEFAULT_PARALLEL_THRESHOLD
+  }
+}
+
+pub fn transform<C: BlockCipher>(cipher: &C, block: &mut [u8], operation: Operation) {
+  match operation {
+    Operation::Encrypt => cipher.encrypt(block),
+    Operation::Decrypt => cipher.decrypt(block),
+  }
+}
+
+// The modes that work with any block size. The initialization vector and the
+// initial counter block must be exactly one block long.
+#[derive(Clone, Debug)]
+pub enum Mode {
+  ECB,
+  CBC(Vec<u8>),
+  // Initial counter block, incremented as a big endian integer
+  CTR(Vec<u8>),
+  // Cipher feedback over whole blocks
+  CFB(Vec<u8>),
+  // Cipher feedback one byte at a time
+  CFB8(Vec<u8>),
+  OFB(Vec<u8>),
+  // Propagating CBC: both the previous plaintext and ciphertext block are
+  // mixed into the next one.
+  PCBC(Vec<u8>),
+  // CBC with ciphertext stealing, which takes any message of at least one
+  // block without padding. The variants only differ in the order of the last
+  // two ciphertext blocks: CS1 keeps the partial block first, CS3 always
+  // swaps them and CS2 swaps them only if the last one is partial.
+  CBCCS1(Vec<u8>),
+  CBCCS2(Vec<u8>),
+  CBCCS3(Vec<u8>),
+}
+
+// The modes that turn the cipher into a stream cipher only ever use it
+// forwards.
+pub fn block_operation(operation: Operation, mode: &Mode) -> Operation {
+  match *mode {
+    Mode::CTR(_) | Mode::CFB(_) | Mode::CFB8(_) | Mode::OFB(_) => Operation::Encrypt,
+    _ => operation,
+  }
+}
+
+// ECB, CBC and PCBC need the plaintext padded to a whole number of blocks,
+// while the stream modes and ciphertext stealing don't.
+pub fn is_padded(mode: &Mode) -> bool {
+  matches!(*mode, Mode::ECB | Mode::CBC(_) | Mode::PCBC(_))
+}
+
+pub trait CipherModeImpl {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8>;
+}
+
+pub fn cipher_mode_impl(
+  mode: Mode,
+  block_size: usize,
+  operation: Operation,
+  parallel_threshold: usize,
+) -> Box<dyn CipherModeImpl> {
+  if let Mode::CBC(ref iv)
+  | Mode::CTR(ref iv)
+  | Mode::CFB(ref iv)
+  | Mode::CFB8(ref iv)
+  | Mode::OFB(ref iv)
+  | Mode::PCBC(ref iv)
+  | Mode::CBCCS1(ref iv)
+  | Mode::CBCCS2(ref iv)
+  | Mode::CBCCS3(ref iv) = mode
+  {
+    assert_eq!(block_size, iv.len(), "IV must be one block long");
+  }
+  match mode {
+    Mode::ECB => Box::new(ECBCipherMode {
+      block_size,
+      parallel_threshold,
+    }),
+    Mode::CBC(iv) => Box::new(CBCCipherMode {
+      initialization_vector: iv,
+      operation,
+      parallel_threshold,
+    }),
+    Mode::CFB(iv) => Box::new(CFBCipherMode {
+      register: iv,
+      segment_size: block_size,
+      operation,
+    }),
+    Mode::CFB8(iv) => Box::new(CFBCipherMode {
+      register: iv,
+      segment_size: 1,
+      operation,
+    }),
+    Mode::OFB(iv) => Box::new(OFBCipherMode { register: iv }),
+    Mode::PCBC(iv) => Box::new(PCBCCipherMode {
+      vector: iv,
+      operation,
+    }),
+    Mode::CBCCS1(iv) => Box::new(CBCCSCipherMode::new(
+      iv,
+      operation,
+      parallel_threshold,
+      false,
+      false,
+    )),
+    Mode::CBCCS2(iv) => Box::new(CBCCSCipherMode::new(
+      iv,
+      operation,
+      parallel_threshold,
+      false,
+      true,
+    )),
+    Mode::CBCCS3(iv) => Box::new(CBCCSCipherMode::new(
+      iv,
+      operation,
+      parallel_threshold,
+      true,
+      true,
+    )),
+    Mode::CTR(counter) => {
+      let counter_size = counter.len();
+      Box::new(CTRCipherMode::new(
+        counter,
+        counter_size,
+        parallel_threshold,
+      ))
+    }
+  }
+}
+
+pub fn perform<C: BlockCipher>(
+  cipher: &C,
+  data: &[u8],
+  operation: Operation,
+  mode: Mode,
+) -> Vec<u8> {
+  let block_operation = block_operation(operation, &mode);
+  let mut cipher_mode_impl =
+    cipher_mode_impl(mode, C::BLOCK_SIZE, operation, cipher.parallel_threshold());
+  cipher_mode_impl.transform_chunks(data, &|block| transform(cipher, block, block_operation))
+}
+
+struct ECBCipherMode {
+  block_size: usize,
+  parallel_threshold: usize,
+}
+
+impl CipherModeImpl for ECBCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let mut v = data.to_vec();
+    if data.len() >= self.parallel_threshold {
+      v.par_chunks_mut(self.block_size).for_each(transform);
+    } else {
+      v.chunks_mut(self.block_size).for_each(transform);
+    }
+    v
+  }
+}
+
+struct CBCCipherMode {
+  initialization_vector: Vec<u8>,
+  operation: Operation,
+  parallel_threshold: usize,
+}
+
+impl CBCCipherMode {
+  fn transform(&mut self, chunk: &[u8], transform: &dyn Fn(&mut [u8])) -> Vec<u8> {
+    match self.operation {
+      Operation::Encrypt => {
+        xor::buffer_mut(&mut self.initialization_vector, xor::Key::FullBuffer(chunk));
+        transform(&mut self.initialization_vector);
+        self.initialization_vector.clone()
+      }
+      Operation::Decrypt => {
+        let mut plaintext = chunk.to_vec();
+        transform(&mut plaintext);
+        xor::buffer_mut(
+          &mut plaintext,
+          xor::Key::FullBuffer(&self.initialization_vector),
+        );
+        self.initialization_vector = chunk.to_vec();
+        plaintext
+      }
+    }
+  }
+}
+
+impl CipherModeImpl for CBCCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let block_size = self.initialization_vector.len();
+    // Decryption only chains through the ciphertext, which is all known up
+    // front.
+    if let Operation::Decrypt = self.operation {
+      if data.len() >= self.parallel_threshold && !data.is_empty() {
+        let mut v = data.to_vec();
+        let iv = &self.initialization_vector;
+        v.par_chunks_mut(block_size)
+          .enumerate()
+          .for_each(|(i, block)| {
+            transform(block);
+            let previous = if i == 0 {
+              &iv[..]
+            } else {
+              &data[block_size * (i - 1)..block_size * i]
+            };
+            xor::buffer_mut(block, xor::Key::FullBuffer(previous));
+          });
+        self.initialization_vector = data[data.len() - block_size..].to_vec();
+        return v;
+      }
+    }
+    let mut v = Vec::with_capacity(data.len());
+    for chunk in data.chunks(block_size) {
+      v.extend(self.transform(chunk, transform));
+    }
+    v
+  }
+}
+
+pub struct CTRCipherMode {
+  counter: Vec<u8>,
+  // How many of the trailing bytes of the counter block are incremented
+  counter_size: usize,
+  parallel_threshold: usize,
+}
+
+impl CTRCipherMode {
+  pub fn new(counter: Vec<u8>, counter_size: usize, parallel_threshold: usize) -> CTRCipherMode {
+    assert!(counter_size <= counter.len());
+    CTRCipherMode {
+      counter,
+      counter_size,
+      parallel_threshold,
+    }
+  }
+}
+
+fn increment_counter(counter: &mut [u8], counter_size: usize) {
+  let start = counter.len() - counter_size;
+  for byte in counter[start..].iter_mut().rev() {
+    *byte = byte.wrapping_add(1);
+    if *byte != 0 {
+      break;
+    }
+  }
+}
+
+// The counter block n blocks further along.
+fn add_to_counter(counter: &[u8], counter_size: usize, mut n: u64) -> Vec<u8> {
+  let mut result = counter.to_vec();
+  let start = result.len() - counter_size;
+  let mut carry = 0;
+  for byte in result[start..].iter_mut().rev() {
+    let sum = u16::from(*byte) + (n & 0xff) as u16 + carry;
+    *byte = sum as u8;
+    carry = sum >> 8;
+    n >>= 8;
+  }
+  result
+}
+
+impl CipherModeImpl for CTRCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let block_size = self.counter.len();
+    let mut v = data.to_vec();
+    if data.len() >= self.parallel_threshold {
+      let counter = &self.counter;
+      let counter_size = self.counter_size;
+      v.par_chunks_mut(block_size)
+        .enumerate()
+        .for_each(|(i, chunk)| {
+          let mut keystream = add_to_counter(counter, counter_size, i as u64);
+          transform(&mut keystream);
+          xor::buffer_mut(chunk, xor::Key::FullBuffer(&keystream));
+        });
+      let blocks = data.chunks(block_size).count() as u64;
+      self.counter = add_to_counter(counter, counter_size, blocks);
+      return v;
+    }
+    for chunk in v.chunks_mut(block_size) {
+      let mut keystream = self.counter.clone();
+      transform(&mut keystream);
+      xor::buffer_mut(chunk, xor::Key::FullBuffer(&keystream));
+      increment_counter(&mut self.counter, self.counter_size);
+    }
+    v
+  }
+}
+
+struct CFBCipherMode {
+  // The last block's worth of ciphertext
+  register: Vec<u8>,
+  // How many bytes are encrypted with each block of keystream
+  segment_size: usize,
+  operation: Operation,
+}
+
+impl CipherModeImpl for CFBCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let mut v = data.to_vec();
+    for segment in v.chunks_mut(self.segment_size) {
+      let mut keystream = self.register.clone();
+      transform(&mut keystream);
+      let ciphertext = match self.operation {
+        Operation::Encrypt => {
+          xor::buffer_mut(segment, xor::Key::FullBuffer(&keystream));
+          segment.to_vec()
+        }
+        Operation::Decrypt => {
+          let ciphertext = segment.to_vec();
+          xor::buffer_mut(segment, xor::Key::FullBuffer(&keystream));
+          ciphertext
+        }
+      };
+      self.register.drain(..ciphertext.len());
+      self.register.extend(ciphertext);
+    }
+    v
+  }
+}
+
+struct OFBCipherMode {
+  register: Vec<u8>,
+}
+
+impl CipherModeImpl for OFBCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let mut v = data.to_vec();
+    for chunk in v.chunks_mut(self.register.len()) {
+      transform(&mut self.register);
+      xor::buffer_mut(chunk, xor::Key::FullBuffer(&self.register));
+    }
+    v
+  }
+}
+
+struct PCBCCipherMode {
+  // The previous plaintext block xored with the previous ciphertext block
+  vector: Vec<u8>,
+  operation: Operation,
+}
+
+impl CipherModeImpl for PCBCCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let mut v = data.to_vec();
+    for chunk in v.chunks_mut(self.vector.len()) {
+      let input = chunk.to_vec();
+      match self.operation {
+        Operation::Encrypt => {
+          xor::buffer_mut(chunk, xor::Key::FullBuffer(&self.vector));
+          transform(chunk);
+        }
+        Operation::Decrypt => {
+          transform(chunk);
+          xor::buffer_mut(chunk, xor::Key::FullBuffer(&self.vector));
+        }
+      }
+      self.vector = xor::buffer(chunk, xor::Key::FullBuffer(&input));
+    }
+    v
+  }
+}
+
+struct CBCCSCipherMode {
+  cbc: CBCCipherMode,
+  // Whether the last two blocks are swapped when the final one is whole or
+  // partial
+  swaps_full_blocks: bool,
+  swaps_partial_blocks: bool,
+}
+
+impl CBCCSCipherMode {
+  fn new(
+    iv: Vec<u8>,
+    operation: Operation,
+    parallel_threshold: usize,
+    swaps_full_blocks: bool,
+    swaps_partial_blocks: bool,
+  ) -> CBCCSCipherMode {
+    CBCCSCipherMode {
+      cbc: CBCCipherMode {
+        initialization_vector: iv,
+        operation,
+        parallel_threshold,
+      },
+      swaps_full_blocks,
+      swaps_partial_blocks,
+    }
+  }
+
+  fn swaps(&self, last_block_size: usize, block_size: usize) -> bool {
+    if last_block_size == block_size {
+      self.swaps_full_blocks
+    } else {
+      self.swaps_partial_blocks
+    }
+  }
+}
+
+impl CipherModeImpl for CBCCSCipherMode {
+  fn transform_chunks(&mut self, data: &[u8], transform: &(dyn Fn(&mut [u8]) + Sync)) -> Vec<u8> {
+    let block_size = self.cbc.initialization_vector.len();
+    assert!(
+      data.len() >= block_size,
+      "Ciphertext stealing needs at least one whole block, got {} bytes",
+      data.len()
+    );
+    if data.len() == block_size {
+      return self.cbc.transform_chunks(data, transform);
+    }
+    // The message ends in a block of last_block_size bytes, preceded by a
+    // whole one starting at penultimate.
+    let last_block_size = (data.len() - 1) % block_size + 1;
+    let penultimate = data.len() - last_block_size - block_size;
+    let swaps = self.swaps(last_block_size, block_size);
+    match self.cbc.operation {
+      Operation::Encrypt => {
+        // Encrypt as CBC with the last block padded with zeros, then drop
+        // the tail of the second to last ciphertext block, which decryption
+        // recovers from the last one.
+        let mut padded = data.to_vec();
+        padded.resize(penultimate + 2 * block_size, 0);
+        let ciphertext = self.cbc.transform_chunks(&padded, transform);
+        let stolen = &ciphertext[penultimate..penultimate + last_block_size];
+        let last = &ciphertext[penultimate + block_size..];
+        let mut v = ciphertext[..penultimate].to_vec();
+        if swaps {
+          v.extend_from_slice(last);
+          v.extend_from_slice(stolen);
+        } else {
+          v.extend_from_slice(stolen);
+          v.extend_from_slice(last);
+        }
+        v
+      }
+      Operation::Decrypt => {
+        let (stolen, last) = if swaps {
+          let (last, stolen) = data[penultimate..].split_at(block_size);
+          (stolen, last)
+        } else {
+          data[penultimate..].split_at(last_block_size)
+        };
+        // The last block decrypts to the zero padded plaintext xored with the
+        // whole second to last ciphertext block, whose missing tail is
+        // therefore left over in the padding.
+        let mut decrypted_last = last.to_vec();
+        transform(&mut decrypted_last);
+        let mut previous = stolen.to_vec();
+        previous.extend_from_slice(&decrypted_last[last_block_size..]);
+        let mut ciphertext = data[..penultimate].to_vec();
+        ciphertext.extend_from_slice(&previous);
+        let mut v = self.cbc.transform_chunks(&ciphertext, transform);
+        v.extend(xor::buffer(
+          &decrypted_last[..last_block_size],
+          xor::Key::FullBuffer(stolen),
+        ));
+        v
+      }
+    }
+  }
+}
+
+#[test]
+fn test_increment_counter() {
+  let mut counter = [0xff; 16];
+  counter[11] = 0x12;
+  increment_counter(&mut counter, 4);
+  assert_eq!([0xff, 0xff, 0xff, 0x12, 0, 0, 0, 0], counter[8..]);
+  increment_counter(&mut counter, 16);
+  assert_eq!([0xff, 0xff, 0xff, 0x12, 0, 0, 0, 1], counter[8..]);
+  let counter = add_to_counter(&counter, 4, 0x1_0000_00ff);
+  assert_eq!([0xff, 0xff, 0xff, 0x12, 0, 0, 1, 0], counter[8..]);
+}
+
+#[test]
+fn aes_matches_specialized_modes() {
+  use rand::{thread_rng, Rng};
+  use util;
+  let mut rng = thread_rng();
+  let key = util::gen_random_bytes(&mut rng, 16);
+  let iv: [u8; 16] = rng.gen();
+  let data = util::gen_random_bytes(&mut rng, 100);
+  let cipher = aes::Cipher::new(&key);
+  let blocks = &data[..96];
+  assert_eq!(
+    aes::perform(blocks, &key, Operation::Encrypt, aes::CipherMode::ECB),
+    perform(&cipher, blocks, Operation::Encrypt, Mode::ECB)
+  );
+  assert_eq!(
+    aes::perform(blocks, &key, Operation::Encrypt, aes::CipherMode::CBC(iv)),
+    perform(&cipher, blocks, Operation::Encrypt, Mode::CBC(iv.to_vec()))
+  );
+  assert_eq!(
+    aes::perform(&data, &key, Operation::Encrypt, aes::CipherMode::CTR(iv)),
+    perform(&cipher, &data, Operation::Encrypt, Mode::CTR(iv.to_vec()))
+  );
+}
+
+#[cfg(test)]
+mod qctests {
+  use super::{is_padded, perform, BlockCipher, Mode};
+  use aes::Operation;
+  use pkcs7;
+  use spn;
+  use xtea;
+
+  fn round_trips<C: BlockCipher>(cipher: &C, iv: &[u8], data: &[u8]) -> bool {
+    let modes = [
+      Mode::ECB,
+      Mode::CBC(iv.to_vec()),
+      Mode::CTR(iv.to_vec()),
+      Mode::CFB(iv.to_vec()),
+      Mode::CFB8(iv.to_vec()),
+      Mode::OFB(iv.to_vec()),
+      Mode::PCBC(iv.to_vec()),
+      Mode::CBCCS1(iv.to_vec()),
+      Mode::CBCCS2(iv.to_vec()),
+      Mode::CBCCS3(iv.to_vec()),
+    ];
+    modes.iter().all(|mode| {
+      let is_stealing = matches!(*mode, Mode::CBCCS1(_) | Mode::CBCCS2(_) | Mode::CBCCS3(_));
+      if is_stealing && data.len() < C::BLOCK_SIZE {
+        return true;
+      }
+      let plaintext = if is_padded(mode) {
+        pkcs7::pad(data, C::BLOCK_SIZE)
+      } else {
+        data.to_vec()
+      };
+      let ciphertext = perform(cipher, &plaintext, Operation::Encrypt, mode.clone());
+      ciphertext.len() == plaintext.len()
+        && perform(cipher, &ciphertext, Operation::Decrypt, mode.clone()) == plaintext
+    })
+  }
+
+  quickcheck! {
+    fn xtea_round_trips(key: Vec<u8>, iv: Vec<u8>, data: Vec<u8>) -> bool {
+      let mut key = key;
+      key.resize(16, 0);
+      let mut iv = iv;
+      iv.resize(xtea::Cipher::BLOCK_SIZE, 0);
+      round_trips(&xtea::Cipher::new(&key), &iv, &data)
+    }
+
+    fn spn_round_trips(key: u32, iv: u16, data: Vec<u8>) -> bool {
+      let iv = [(iv >> 8) as u8, iv as u8];
+      round_trips(&spn::Cipher::new(key), &iv, &data)
+    }
+  }
+}
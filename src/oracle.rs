@@ -1,8 +1,16 @@
+use aead;
 use aes;
+use hmac;
+use modes::{self, BlockCipher};
+use padding::{self, Padding};
 use pkcs7;
 use rand::prelude::*;
+use rc4;
+use sha1;
+use std::cell::Cell;
 use std::collections::BTreeSet;
 use util;
+use xts;
 
 pub struct OracleResult {
   pub data: Vec<u8>,
@@ -17,6 +25,13 @@ pub trait DecodableOracle {
   fn decode(&self, ciphertext: &[u8]) -> Vec<u8>;
 }
 
+// Lets several wrappers share one oracle and its key.
+impl<O: Oracle + ?Sized> Oracle for &O {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    (**self).encode(input)
+  }
+}
+
 pub fn determine_block_size_and_input_offset(o: &Oracle) -> (usize, usize) {
   let mut buffer = vec![0; 0];
   let initial_length = o.encode(&buffer).data.len();
@@ -49,7 +64,7 @@ pub fn is_using_ecb(o: &Oracle) -> bool {
   let block_size = determine_block_size(o);
   let buffer = vec![0; block_size * 3];
   let encoded_buffer = o.encode(&buffer).data;
-  analysis::likely_aes_ecb_score(&encoded_buffer) > 0
+  analysis::likely_ecb_score(&encoded_buffer, block_size) > 0
 }
 
 pub struct Random;
@@ -89,7 +104,7 @@ impl Oracle for Random {
 }
 
 pub struct AES128 {
-  key: Vec<u8>,
+  cipher: aes::Cipher,
   cipher_mode: aes::CipherMode,
 }
 
@@ -98,7 +113,7 @@ impl AES128 {
     let mut rng = thread_rng();
     let key = util::gen_random_bytes(&mut rng, 16);
     AES128 {
-      key,
+      cipher: aes::Cipher::new(&key),
       cipher_mode: aes::CipherMode::ECB,
     }
   }
@@ -106,21 +121,55 @@ impl AES128 {
     let mut rng = thread_rng();
     let key = util::gen_random_bytes(&mut rng, 16);
     AES128 {
-      key,
+      cipher: aes::Cipher::new(&key),
       cipher_mode: aes::CipherMode::CBC(util::convert_to_fixed_array(&iv)),
     }
   }
+
+  pub fn with_mode(cipher_mode: aes::CipherMode) -> AES128 {
+    let key = util::gen_random_bytes(&mut thread_rng(), 16);
+    AES128 {
+      cipher: aes::Cipher::new(&key),
+      cipher_mode,
+    }
+  }
+
+  // Every message is encrypted under the same nonce, which GCM forbids.
+  pub fn with_gcm(nonce: [u8; 12], additional_data: Vec<u8>) -> AES128 {
+    let mut rng = thread_rng();
+    let key = util::gen_random_bytes(&mut rng, 16);
+    AES128 {
+      cipher: aes::Cipher::new(&key),
+      cipher_mode: aes::CipherMode::GCM(nonce, additional_data),
+    }
+  }
+
+  fn is_padded(&self) -> bool {
+    aes::is_padded(&self.cipher_mode)
+  }
+
+  // Decodes a GCM ciphertext, reporting a bad tag rather than panicking.
+  pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, aes::IntegrityError> {
+    match self.cipher_mode {
+      aes::CipherMode::GCM(nonce, ref additional_data) => {
+        self.cipher.gcm_open(nonce, additional_data, ciphertext)
+      }
+      _ => panic!("Only GCM ciphertexts carry a tag to check"),
+    }
+  }
 }
 
 impl Oracle for AES128 {
   fn encode(&self, input: &[u8]) -> OracleResult {
-    let data = pkcs7::pad(&input, 16);
-    let encoded_data = aes::perform(
-      &data,
-      &self.key,
-      aes::Operation::Encrypt,
-      self.cipher_mode.clone(),
-    );
+    let data = if self.is_padded() {
+      pkcs7::pad(&input, 16)
+    } else {
+      input.to_vec()
+    };
+    let encoded_data =
+      self
+        .cipher
+        .perform(&data, aes::Operation::Encrypt, self.cipher_mode.clone());
     OracleResult {
       data: encoded_data,
       is_ecb: true,
@@ -130,17 +179,693 @@ impl Oracle for AES128 {
 
 impl DecodableOracle for AES128 {
   fn decode(&self, ciphertext: &[u8]) -> Vec<u8> {
-    let mut decoded_data = aes::perform(
+    let mut decoded_data = self.cipher.perform(
       &ciphertext,
-      &self.key,
       aes::Operation::Decrypt,
       self.cipher_mode.clone(),
     );
-    pkcs7::unpad_mut(&mut decoded_data, 16);
+    if self.is_padded() {
+      pkcs7::unpad_mut(&mut decoded_data, 16);
+    }
+    decoded_data
+  }
+}
+
+// Encrypts under any block cipher in one of the generic modes, padding to its
+// block size where the mode needs it.
+pub struct Block<C: BlockCipher> {
+  cipher: C,
+  mode: modes::Mode,
+}
+
+impl<C: BlockCipher> Block<C> {
+  pub fn new(cipher: C, mode: modes::Mode) -> Self {
+    Block { cipher, mode }
+  }
+}
+
+impl<C: BlockCipher> Oracle for Block<C> {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let data = if modes::is_padded(&self.mode) {
+      pkcs7::pad(input, C::BLOCK_SIZE)
+    } else {
+      input.to_vec()
+    };
+    OracleResult {
+      data: modes::perform(
+        &self.cipher,
+        &data,
+        aes::Operation::Encrypt,
+        self.mode.clone(),
+      ),
+      is_ecb: matches!(self.mode, modes::Mode::ECB),
+    }
+  }
+}
+
+impl<C: BlockCipher> DecodableOracle for Block<C> {
+  fn decode(&self, ciphertext: &[u8]) -> Vec<u8> {
+    let mut decoded_data = modes::perform(
+      &self.cipher,
+      ciphertext,
+      aes::Operation::Decrypt,
+      self.mode.clone(),
+    );
+    if modes::is_padded(&self.mode) {
+      pkcs7::unpad_mut(&mut decoded_data, C::BLOCK_SIZE);
+    }
     decoded_data
   }
 }
 
+#[test]
+fn test_block_oracles() {
+  use spn;
+  use xtea;
+  let key = util::gen_random_bytes(&mut thread_rng(), 16);
+  let xtea_ecb = Block::new(xtea::Cipher::new(&key), modes::Mode::ECB);
+  assert_eq!(8, determine_block_size(&xtea_ecb));
+  assert!(is_using_ecb(&xtea_ecb));
+  let spn_cbc = Block::new(spn::Cipher::new(0xdead_beef), modes::Mode::CBC(vec![1, 2]));
+  assert_eq!(2, determine_block_size(&spn_cbc));
+  assert!(!is_using_ecb(&spn_cbc));
+  let message = b"Reusable oracles";
+  assert_eq!(
+    &message[..],
+    &spn_cbc.decode(&spn_cbc.encode(message).data)[..]
+  );
+}
+
+// Encrypts single blocks under a fixed AES-128 key, returning each ciphertext
+// along with the one produced when a random byte of the state is corrupted
+// just before the ninth round's MixColumns.
+pub struct FaultyAES128 {
+  cipher: aes::Cipher,
+}
+
+impl FaultyAES128 {
+  pub fn new() -> FaultyAES128 {
+    let key = util::gen_random_bytes(&mut thread_rng(), 16);
+    FaultyAES128 {
+      cipher: aes::Cipher::with_backend(&key, aes::Backend::Reference),
+    }
+  }
+
+  pub fn encrypt(&self, block: &[u8; 16]) -> ([u8; 16], [u8; 16]) {
+    let mut rng = thread_rng();
+    let index = usize::from(rng.gen_range(0u8, 16));
+    let difference = rng.gen_range(1u16, 256) as u8;
+    (
+      self.cipher.encrypt_block(block),
+      self
+        .cipher
+        .encrypt_block_with_fault(block, 9, index, difference),
+    )
+  }
+}
+
+impl Default for FaultyAES128 {
+  fn default() -> Self {
+    FaultyAES128::new()
+  }
+}
+
+// A disk image encrypted sector by sector with AES-XTS under a random key.
+// The owner reads and writes plaintext through the cipher, while whoever gets
+// hold of the image can read and overwrite the raw sectors.
+pub struct Disk {
+  cipher: xts::Cipher,
+  sectors: Vec<Vec<u8>>,
+}
+
+impl Disk {
+  pub const SECTOR_SIZE: usize = 512;
+
+  pub fn new(sector_count: usize) -> Disk {
+    let key = util::gen_random_bytes(&mut thread_rng(), 32);
+    let cipher = xts::Cipher::new(&key);
+    let sectors = (0..sector_count)
+      .map(|sector| cipher.encrypt_sector(sector as u64, &[0; Disk::SECTOR_SIZE]))
+      .collect();
+    Disk { cipher, sectors }
+  }
+
+  pub fn read(&self, sector: usize) -> Vec<u8> {
+    self
+      .cipher
+      .decrypt_sector(sector as u64, &self.sectors[sector])
+  }
+
+  pub fn write(&mut self, sector: usize, data: &[u8]) {
+    assert_eq!(Disk::SECTOR_SIZE, data.len());
+    self.sectors[sector] = self.cipher.encrypt_sector(sector as u64, data);
+  }
+
+  pub fn raw_sector(&self, sector: usize) -> Vec<u8> {
+    self.sectors[sector].clone()
+  }
+
+  pub fn set_raw_sector(&mut self, sector: usize, ciphertext: &[u8]) {
+    assert_eq!(Disk::SECTOR_SIZE, ciphertext.len());
+    self.sectors[sector] = ciphertext.to_vec();
+  }
+}
+
+// Seals every message with encrypt-then-MAC under random keys.
+pub struct EncryptThenMac {
+  aead: aead::EncryptThenMac,
+}
+
+impl EncryptThenMac {
+  pub fn new() -> EncryptThenMac {
+    let mut rng = thread_rng();
+    let encryption_key = util::gen_random_bytes(&mut rng, 16);
+    let mac_key = util::gen_random_bytes(&mut rng, 16);
+    EncryptThenMac {
+      aead: aead::EncryptThenMac::new(&encryption_key, &mac_key, aead::Mode::CBC),
+    }
+  }
+
+  pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, aead::Error> {
+    self.aead.open(ciphertext)
+  }
+}
+
+impl Default for EncryptThenMac {
+  fn default() -> Self {
+    EncryptThenMac::new()
+  }
+}
+
+impl Oracle for EncryptThenMac {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    OracleResult {
+      data: self.aead.seal(input),
+      is_ecb: false,
+    }
+  }
+}
+
+impl DecodableOracle for EncryptThenMac {
+  fn decode(&self, ciphertext: &[u8]) -> Vec<u8> {
+    self.open(ciphertext).unwrap()
+  }
+}
+
+// HMAC-SHA1 along with the number of SHA-1 compressions it takes, which
+// stands in for its timing.
+fn hmac_sha1_timed(key: &[u8], message: &[u8]) -> (Vec<u8>, usize) {
+  let compressions = Cell::new(0);
+  let hash = |data: &[u8]| {
+    compressions.set(compressions.get() + sha1::pad(data).len() / sha1::BLOCK_SIZE);
+    sha1::hash(data)
+  };
+  let tag = hmac::hmac(&hash, sha1::BLOCK_SIZE, key, message);
+  (tag, compressions.get())
+}
+
+// MAC-then-encrypt, as in TLS up to 1.2: the tag is appended to the plaintext
+// and both are encrypted with AES-CBC. Decryption has to check the padding
+// before it can even find the tag, and says which check failed.
+pub struct MacThenEncrypt {
+  encryption_key: Vec<u8>,
+  mac_key: Vec<u8>,
+}
+
+impl MacThenEncrypt {
+  pub fn new() -> MacThenEncrypt {
+    let mut rng = thread_rng();
+    MacThenEncrypt {
+      encryption_key: util::gen_random_bytes(&mut rng, 16),
+      mac_key: util::gen_random_bytes(&mut rng, 16),
+    }
+  }
+
+  // Opens a ciphertext and also returns the number of SHA-1 compressions
+  // this takes. Bad padding is caught before the MAC is computed, so it is
+  // much cheaper to report than a bad MAC.
+  pub fn open_timed(&self, ciphertext: &[u8]) -> (Result<Vec<u8>, aead::Error>, usize) {
+    let mut plaintext = match aead::decrypt(&self.encryption_key, aead::Mode::CBC, ciphertext) {
+      Ok(plaintext) => plaintext,
+      Err(e) => return (Err(e), 0),
+    };
+    if plaintext.len() < aead::TAG_SIZE {
+      return (Err(aead::Error::BadMac), 0);
+    }
+    let tag = plaintext.split_off(plaintext.len() - aead::TAG_SIZE);
+    let (expected_tag, cost) = hmac_sha1_timed(&self.mac_key, &plaintext);
+    if aead::tags_match(&tag, &expected_tag) {
+      (Ok(plaintext), cost)
+    } else {
+      (Err(aead::Error::BadMac), cost)
+    }
+  }
+
+  pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, aead::Error> {
+    self.open_timed(ciphertext).0
+  }
+}
+
+impl Default for MacThenEncrypt {
+  fn default() -> Self {
+    MacThenEncrypt::new()
+  }
+}
+
+impl Oracle for MacThenEncrypt {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let mut plaintext = input.to_vec();
+    plaintext.extend(aead::mac(&self.mac_key, input));
+    OracleResult {
+      data: aead::encrypt(&self.encryption_key, aead::Mode::CBC, &plaintext),
+      is_ecb: false,
+    }
+  }
+}
+
+impl DecodableOracle for MacThenEncrypt {
+  fn decode(&self, ciphertext: &[u8]) -> Vec<u8> {
+    self.open(ciphertext).unwrap()
+  }
+}
+
+// Encrypt-and-MAC, as in SSH: the tag of the plaintext is sent in the clear
+// next to its AES-CBC encryption, so equal messages carry equal tags.
+pub struct EncryptAndMac {
+  encryption_key: Vec<u8>,
+  mac_key: Vec<u8>,
+}
+
+impl EncryptAndMac {
+  pub fn new() -> EncryptAndMac {
+    let mut rng = thread_rng();
+    EncryptAndMac {
+      encryption_key: util::gen_random_bytes(&mut rng, 16),
+      mac_key: util::gen_random_bytes(&mut rng, 16),
+    }
+  }
+
+  pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, aead::Error> {
+    if ciphertext.len() < aead::TAG_SIZE {
+      return Err(aead::Error::InvalidLength(ciphertext.len()));
+    }
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - aead::TAG_SIZE);
+    let plaintext = aead::decrypt(&self.encryption_key, aead::Mode::CBC, ciphertext)?;
+    if !aead::tags_match(tag, &aead::mac(&self.mac_key, &plaintext)) {
+      return Err(aead::Error::BadMac);
+    }
+    Ok(plaintext)
+  }
+}
+
+impl Default for EncryptAndMac {
+  fn default() -> Self {
+    EncryptAndMac::new()
+  }
+}
+
+impl Oracle for EncryptAndMac {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let mut data = aead::encrypt(&self.encryption_key, aead::Mode::CBC, input);
+    data.extend(aead::mac(&self.mac_key, input));
+    OracleResult {
+      data,
+      is_ecb: false,
+    }
+  }
+}
+
+impl DecodableOracle for EncryptAndMac {
+  fn decode(&self, ciphertext: &[u8]) -> Vec<u8> {
+    self.open(ciphertext).unwrap()
+  }
+}
+
+#[test]
+fn mac_orderings() {
+  let message = b"user=alice;role=guest";
+  let etm = EncryptThenMac::new();
+  let mte = MacThenEncrypt::new();
+  let eam = EncryptAndMac::new();
+  assert_eq!(&message[..], &etm.decode(&etm.encode(message).data)[..]);
+  assert_eq!(&message[..], &mte.decode(&mte.encode(message).data)[..]);
+  assert_eq!(&message[..], &eam.decode(&eam.encode(message).data)[..]);
+
+  // Tampering with the last byte of the second to last block changes the
+  // last byte of the padding. Encrypt-then-MAC rejects every attempt before
+  // decrypting, while MAC-then-encrypt reveals which guesses left the padding
+  // valid, which is all a padding oracle attack needs.
+  let etm_ciphertext = etm.encode(message).data;
+  let mte_ciphertext = mte.encode(message).data;
+  let index = mte_ciphertext.len() - 17;
+  let mut mte_errors = Vec::new();
+  for guess in 1..=255u8 {
+    let mut tampered = etm_ciphertext.clone();
+    tampered[etm_ciphertext.len() - aead::TAG_SIZE - 17] ^= guess;
+    assert_eq!(Err(aead::Error::BadMac), etm.open(&tampered));
+    let mut tampered = mte_ciphertext.clone();
+    tampered[index] ^= guess;
+    let (result, cost) = mte.open_timed(&tampered);
+    mte_errors.push((result.unwrap_err(), cost));
+  }
+  assert!(mte_errors
+    .iter()
+    .any(|&(e, _)| e == aead::Error::BadPadding));
+  assert!(mte_errors.iter().any(|&(e, _)| e == aead::Error::BadMac));
+  // Even with the same error for both, the time taken would tell them apart,
+  // as in Lucky Thirteen.
+  for &(e, cost) in mte_errors.iter() {
+    assert_eq!(e == aead::Error::BadPadding, cost == 0);
+  }
+
+  // Encrypt-and-MAC tags give away which messages repeat.
+  let tag = |ciphertext: &[u8]| ciphertext[ciphertext.len() - aead::TAG_SIZE..].to_vec();
+  assert_eq!(
+    tag(&eam.encode(message).data),
+    tag(&eam.encode(message).data)
+  );
+  assert_ne!(
+    tag(&etm.encode(message).data),
+    tag(&etm.encode(message).data)
+  );
+}
+
+// A TLS 1.2 style CBC record layer with HMAC-SHA1 over a 13 byte header and
+// the message. A record with bad padding gets its MAC checked as if it had
+// none, as RFC 5246 suggests, and is rejected with the same error, but the
+// MAC then covers a few more bytes. The cost of opening a record is the
+// number of SHA-1 compressions this takes, standing in for its timing.
+pub struct TlsRecord {
+  encryption_key: Vec<u8>,
+  mac_key: Vec<u8>,
+}
+
+impl TlsRecord {
+  pub const HEADER_SIZE: usize = 13;
+
+  pub fn new() -> TlsRecord {
+    let mut rng = thread_rng();
+    TlsRecord {
+      encryption_key: util::gen_random_bytes(&mut rng, 16),
+      mac_key: util::gen_random_bytes(&mut rng, 16),
+    }
+  }
+
+  // The sequence number, content type, version and length of the message.
+  fn header(length: usize) -> Vec<u8> {
+    let mut header = vec![0; 8];
+    header.extend_from_slice(&[23, 3, 3, (length >> 8) as u8, length as u8]);
+    header
+  }
+
+  fn mac(&self, message: &[u8]) -> (Vec<u8>, usize) {
+    let mut input = TlsRecord::header(message.len());
+    input.extend_from_slice(message);
+    hmac_sha1_timed(&self.mac_key, &input)
+  }
+
+  pub fn seal(&self, message: &[u8]) -> Vec<u8> {
+    let mut plaintext = message.to_vec();
+    plaintext.extend(self.mac(message).0);
+    aead::encrypt(&self.encryption_key, aead::Mode::CBC, &plaintext)
+  }
+
+  // Opens a record and also returns its cost.
+  pub fn open_timed(&self, record: &[u8]) -> (Result<Vec<u8>, aead::Error>, usize) {
+    if record.len() < 32 || !record.len().is_multiple_of(16) {
+      return (Err(aead::Error::InvalidLength(record.len())), 0);
+    }
+    let (iv, ciphertext) = record.split_at(16);
+    let plaintext = aes::perform(
+      ciphertext,
+      &self.encryption_key,
+      aes::Operation::Decrypt,
+      aes::CipherMode::CBC(util::convert_to_fixed_array(iv)),
+    );
+    let is_padding_valid = pkcs7::is_valid(&plaintext, 16);
+    let padding_length = if is_padding_valid {
+      plaintext[plaintext.len() - 1] as usize
+    } else {
+      0
+    };
+    if plaintext.len() < padding_length + aead::TAG_SIZE {
+      return (Err(aead::Error::BadMac), 0);
+    }
+    let message_length = plaintext.len() - padding_length - aead::TAG_SIZE;
+    let (message, tag) = plaintext[..plaintext.len() - padding_length].split_at(message_length);
+    let (expected_tag, cost) = self.mac(message);
+    if is_padding_valid && aead::tags_match(tag, &expected_tag) {
+      (Ok(message.to_vec()), cost)
+    } else {
+      (Err(aead::Error::BadMac), cost)
+    }
+  }
+
+  pub fn open(&self, record: &[u8]) -> Result<Vec<u8>, aead::Error> {
+    self.open_timed(record).0
+  }
+}
+
+impl Default for TlsRecord {
+  fn default() -> Self {
+    TlsRecord::new()
+  }
+}
+
+impl Oracle for TlsRecord {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    OracleResult {
+      data: self.seal(input),
+      is_ecb: false,
+    }
+  }
+}
+
+impl DecodableOracle for TlsRecord {
+  fn decode(&self, record: &[u8]) -> Vec<u8> {
+    self.open(record).unwrap()
+  }
+}
+
+// Encrypts with AES-CBC under a random IV, which leads the ciphertext, after
+// padding with any scheme. Apart from decoding, all it says about a
+// ciphertext is whether its padding is well formed.
+pub struct CBCPadding<P: Padding> {
+  key: Vec<u8>,
+  padding: P,
+}
+
+impl<P: Padding> CBCPadding<P> {
+  pub fn new(padding: P) -> Self {
+    CBCPadding {
+      key: util::gen_random_bytes(&mut thread_rng(), 16),
+      padding,
+    }
+  }
+
+  fn decrypt(&self, ciphertext: &[u8]) -> Vec<u8> {
+    let (iv, ciphertext) = ciphertext.split_at(16);
+    aes::perform(
+      ciphertext,
+      &self.key,
+      aes::Operation::Decrypt,
+      aes::CipherMode::CBC(util::convert_to_fixed_array(iv)),
+    )
+  }
+
+  pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, aead::Error> {
+    if ciphertext.len() < 32 || !ciphertext.len().is_multiple_of(16) {
+      return Err(aead::Error::InvalidLength(ciphertext.len()));
+    }
+    self
+      .padding
+      .unpad(&self.decrypt(ciphertext), 16)
+      .ok_or(aead::Error::BadPadding)
+  }
+
+  pub fn is_padding_valid(&self, ciphertext: &[u8]) -> bool {
+    self.open(ciphertext).is_ok()
+  }
+}
+
+impl<P: Padding> Oracle for CBCPadding<P> {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let iv: [u8; 16] = thread_rng().gen();
+    let mut data = iv.to_vec();
+    data.extend(aes::perform(
+      &self.padding.pad(input, 16),
+      &self.key,
+      aes::Operation::Encrypt,
+      aes::CipherMode::CBC(iv),
+    ));
+    OracleResult {
+      data,
+      is_ecb: false,
+    }
+  }
+}
+
+impl<P: Padding> DecodableOracle for CBCPadding<P> {
+  fn decode(&self, ciphertext: &[u8]) -> Vec<u8> {
+    self.open(ciphertext).unwrap()
+  }
+}
+
+// An SSLv3 style CBC record layer: the message and its HMAC, then padding
+// whose last byte is all that gets checked.
+pub struct SSLv3 {
+  cbc: CBCPadding<padding::SSLv3>,
+  mac_key: Vec<u8>,
+}
+
+impl SSLv3 {
+  pub fn new() -> SSLv3 {
+    SSLv3 {
+      cbc: CBCPadding::new(padding::SSLv3),
+      mac_key: util::gen_random_bytes(&mut thread_rng(), 16),
+    }
+  }
+
+  pub fn seal(&self, message: &[u8]) -> Vec<u8> {
+    let mut plaintext = message.to_vec();
+    plaintext.extend(aead::mac(&self.mac_key, message));
+    self.cbc.encode(&plaintext).data
+  }
+
+  pub fn open(&self, record: &[u8]) -> Result<Vec<u8>, aead::Error> {
+    let mut plaintext = self.cbc.open(record)?;
+    if plaintext.len() < aead::TAG_SIZE {
+      return Err(aead::Error::BadMac);
+    }
+    let tag = plaintext.split_off(plaintext.len() - aead::TAG_SIZE);
+    if !aead::tags_match(&tag, &aead::mac(&self.mac_key, &plaintext)) {
+      return Err(aead::Error::BadMac);
+    }
+    Ok(plaintext)
+  }
+}
+
+impl Default for SSLv3 {
+  fn default() -> Self {
+    SSLv3::new()
+  }
+}
+
+impl Oracle for SSLv3 {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    OracleResult {
+      data: self.seal(input),
+      is_ecb: false,
+    }
+  }
+}
+
+impl DecodableOracle for SSLv3 {
+  fn decode(&self, record: &[u8]) -> Vec<u8> {
+    self.open(record).unwrap()
+  }
+}
+
+// Encrypts each message as the next record of a single AES-CBC stream, as
+// SSLv3 and TLS 1.0 do: a record's IV is the last ciphertext block of the
+// one before, which anyone watching the connection has already seen.
+pub struct ChainedCBC {
+  cipher: aes::Cipher,
+  iv: Cell<[u8; 16]>,
+}
+
+impl ChainedCBC {
+  pub fn new() -> ChainedCBC {
+    let mut rng = thread_rng();
+    ChainedCBC {
+      cipher: aes::Cipher::new(&util::gen_random_bytes(&mut rng, 16)),
+      iv: Cell::new(rng.gen()),
+    }
+  }
+}
+
+impl Default for ChainedCBC {
+  fn default() -> Self {
+    ChainedCBC::new()
+  }
+}
+
+impl Oracle for ChainedCBC {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let data = self.cipher.perform(
+      &pkcs7::pad(input, 16),
+      aes::Operation::Encrypt,
+      aes::CipherMode::CBC(self.iv.get()),
+    );
+    self
+      .iv
+      .set(util::convert_to_fixed_array(&data[data.len() - 16..]));
+    OracleResult {
+      data,
+      is_ecb: false,
+    }
+  }
+}
+
+// Has the victim send the attacker's request followed by a secret, the given
+// number of times, on one long CBC connection under a random IV, and returns
+// all of the traffic with the IV first. With a small enough block size this
+// is plenty of ciphertext for blocks to start repeating.
+pub struct RepeatedSecret<C: BlockCipher> {
+  cipher: C,
+  secret: Vec<u8>,
+  count: usize,
+}
+
+impl<C: BlockCipher> RepeatedSecret<C> {
+  pub fn new(cipher: C, secret: Vec<u8>, count: usize) -> Self {
+    RepeatedSecret {
+      cipher,
+      secret,
+      count,
+    }
+  }
+}
+
+impl<C: BlockCipher> Oracle for RepeatedSecret<C> {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let mut request = input.to_vec();
+    request.extend_from_slice(&self.secret);
+    let iv = util::gen_random_bytes(&mut thread_rng(), C::BLOCK_SIZE);
+    let mut data = iv.clone();
+    data.extend(modes::perform(
+      &self.cipher,
+      &pkcs7::pad(&request.repeat(self.count), C::BLOCK_SIZE),
+      aes::Operation::Encrypt,
+      modes::Mode::CBC(iv),
+    ));
+    OracleResult {
+      data,
+      is_ecb: false,
+    }
+  }
+}
+
+// Encrypts every request under a fresh random RC4 key.
+#[derive(Default)]
+pub struct RC4;
+
+impl RC4 {
+  pub fn new() -> RC4 {
+    RC4 {}
+  }
+}
+
+impl Oracle for RC4 {
+  fn encode(&self, input: &[u8]) -> OracleResult {
+    let key = util::gen_random_bytes(&mut thread_rng(), 16);
+    OracleResult {
+      data: rc4::perform(input, &key),
+      is_ecb: false,
+    }
+  }
+}
+
 pub struct ConstantAppend<O: Oracle> {
   oracle: O,
   suffix: Vec<u8>,
@@ -0,0 +1,281 @@
+use pkcs7;
+use rand::{thread_rng, Rng};
+
+pub trait Padding: Sync {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8>;
+
+  // The length of the data once its padding is removed, or None if it isn't
+  // well formed.
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize>;
+
+  // A suffix of the given length that makes a block valid whatever comes
+  // before it, and that stops being valid for almost any change to its first
+  // byte, if the scheme has one.
+  fn forced_suffix(&self, length: usize, block_size: usize) -> Option<Vec<u8>>;
+
+  fn unpad(&self, data: &[u8], block_size: usize) -> Option<Vec<u8>> {
+    self
+      .unpadded_length(data, block_size)
+      .map(|length| data[..length].to_vec())
+  }
+
+  fn is_valid(&self, data: &[u8], block_size: usize) -> bool {
+    self.unpadded_length(data, block_size).is_some()
+  }
+}
+
+fn is_aligned(data: &[u8], block_size: usize) -> bool {
+  !data.is_empty() && data.len().is_multiple_of(block_size)
+}
+
+// The padding length the last byte claims, for the schemes that end with it.
+fn counted_length(data: &[u8], block_size: usize) -> Option<usize> {
+  if !is_aligned(data, block_size) {
+    return None;
+  }
+  let length = data[data.len() - 1] as usize;
+  if length >= 1 && length <= block_size {
+    Some(length)
+  } else {
+    None
+  }
+}
+
+// Pads with copies of a byte and then the padding length.
+fn pad_with_count(data: &[u8], block_size: usize, filler: &mut dyn FnMut() -> u8) -> Vec<u8> {
+  assert!(block_size > 0);
+  assert!(block_size < 256);
+  let length = block_size - data.len() % block_size;
+  let mut padded = data.to_vec();
+  for _ in 1..length {
+    padded.push(filler());
+  }
+  padded.push(length as u8);
+  padded
+}
+
+// n bytes of value n.
+#[derive(Clone, Copy)]
+pub struct PKCS7;
+
+impl Padding for PKCS7 {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+    pkcs7::pad(data, block_size)
+  }
+
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize> {
+    if pkcs7::is_valid(data, block_size) {
+      Some(data.len() - data[data.len() - 1] as usize)
+    } else {
+      None
+    }
+  }
+
+  fn forced_suffix(&self, length: usize, _block_size: usize) -> Option<Vec<u8>> {
+    Some(vec![length as u8; length])
+  }
+}
+
+// Zeros and then the padding length.
+#[derive(Clone, Copy)]
+pub struct ANSIX923;
+
+impl Padding for ANSIX923 {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+    pad_with_count(data, block_size, &mut || 0)
+  }
+
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize> {
+    counted_length(data, block_size)
+      .filter(|&length| {
+        data[data.len() - length..data.len() - 1]
+          .iter()
+          .all(|&byte| byte == 0)
+      })
+      .map(|length| data.len() - length)
+  }
+
+  fn forced_suffix(&self, length: usize, _block_size: usize) -> Option<Vec<u8>> {
+    let mut suffix = vec![0; length];
+    suffix[length - 1] = length as u8;
+    Some(suffix)
+  }
+}
+
+// Random bytes and then the padding length.
+#[derive(Clone, Copy)]
+pub struct ISO10126;
+
+impl Padding for ISO10126 {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+    let mut rng = thread_rng();
+    pad_with_count(data, block_size, &mut || rng.gen())
+  }
+
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize> {
+    counted_length(data, block_size).map(|length| data.len() - length)
+  }
+
+  // Only the last byte is checked, and any of block_size values will do.
+  fn forced_suffix(&self, _length: usize, _block_size: usize) -> Option<Vec<u8>> {
+    None
+  }
+}
+
+// A single 0x80 byte and then zeros, as in ISO/IEC 7816-4 and the hashes.
+#[derive(Clone, Copy)]
+pub struct ISO7816;
+
+impl Padding for ISO7816 {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+    assert!(block_size > 0);
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while !padded.len().is_multiple_of(block_size) {
+      padded.push(0);
+    }
+    padded
+  }
+
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize> {
+    if !is_aligned(data, block_size) {
+      return None;
+    }
+    let last_block = &data[data.len() - block_size..];
+    match last_block.iter().rposition(|&byte| byte != 0) {
+      Some(i) if last_block[i] == 0x80 => Some(data.len() - block_size + i),
+      _ => None,
+    }
+  }
+
+  fn forced_suffix(&self, length: usize, _block_size: usize) -> Option<Vec<u8>> {
+    let mut suffix = vec![0; length];
+    suffix[0] = 0x80;
+    Some(suffix)
+  }
+}
+
+// Random bytes and then their count, which leaves out the count byte itself,
+// as in SSLv3. Only the last byte can be checked, and it has to be less than
+// the block size.
+#[derive(Clone, Copy)]
+pub struct SSLv3;
+
+impl Padding for SSLv3 {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+    assert!(block_size > 0);
+    assert!(block_size <= 256);
+    let mut rng = thread_rng();
+    let length = block_size - 1 - data.len() % block_size;
+    let mut padded = data.to_vec();
+    for _ in 0..length {
+      padded.push(rng.gen());
+    }
+    padded.push(length as u8);
+    padded
+  }
+
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize> {
+    if !is_aligned(data, block_size) {
+      return None;
+    }
+    let length = data[data.len() - 1] as usize;
+    if length < block_size {
+      Some(data.len() - length - 1)
+    } else {
+      None
+    }
+  }
+
+  fn forced_suffix(&self, _length: usize, _block_size: usize) -> Option<Vec<u8>> {
+    None
+  }
+}
+
+// Zeros up to the end of the block, and nothing for data that already fills
+// it. Every aligned input is valid, and trailing zeros in the data are lost.
+#[derive(Clone, Copy)]
+pub struct Zero;
+
+impl Padding for Zero {
+  fn pad(&self, data: &[u8], block_size: usize) -> Vec<u8> {
+    assert!(block_size > 0);
+    let mut padded = data.to_vec();
+    while !padded.len().is_multiple_of(block_size) {
+      padded.push(0);
+    }
+    padded
+  }
+
+  fn unpadded_length(&self, data: &[u8], block_size: usize) -> Option<usize> {
+    if !data.len().is_multiple_of(block_size) {
+      return None;
+    }
+    let start = data.len().saturating_sub(block_size);
+    let zeros = data[start..]
+      .iter()
+      .rev()
+      .take_while(|&&byte| byte == 0)
+      .count();
+    Some(data.len() - zeros)
+  }
+
+  fn forced_suffix(&self, _length: usize, _block_size: usize) -> Option<Vec<u8>> {
+    None
+  }
+}
+
+#[test]
+fn test_schemes() {
+  let data = b"YELLOW SUBMARINE";
+  let schemes: [(&dyn Padding, &[u8]); 4] = [
+    (&PKCS7, b"YELLOW SUBMARINE\x04\x04\x04\x04"),
+    (&ANSIX923, b"YELLOW SUBMARINE\x00\x00\x00\x04"),
+    (&ISO7816, b"YELLOW SUBMARINE\x80\x00\x00\x00"),
+    (&Zero, b"YELLOW SUBMARINE\x00\x00\x00\x00"),
+  ];
+  for &(scheme, padded) in schemes.iter() {
+    assert_eq!(padded, &scheme.pad(data, 20)[..]);
+    assert_eq!(Some(data.to_vec()), scheme.unpad(padded, 20));
+    assert!(!scheme.is_valid(&padded[1..], 20));
+  }
+  let padded = ISO10126.pad(data, 20);
+  assert_eq!(data[..], padded[..16]);
+  assert_eq!(4, padded[19]);
+  assert_eq!(Some(data.to_vec()), ISO10126.unpad(&padded, 20));
+  let padded = SSLv3.pad(data, 20);
+  assert_eq!(3, padded[19]);
+  assert_eq!(Some(data.to_vec()), SSLv3.unpad(&padded, 20));
+  assert!(!SSLv3.is_valid(b"YELLOW SUBMARINE\x00\x00\x00\x10", 16));
+
+  assert_eq!(data.to_vec(), Zero.pad(data, 16));
+  assert_eq!(b"\x80".to_vec(), ISO7816.pad(b"", 1));
+  assert!(!ANSIX923.is_valid(b"YELLOW SUBMARINE\x00\x01\x00\x04", 20));
+  assert!(!ISO7816.is_valid(b"YELLOW SUBMARINE\x81\x00\x00\x00", 20));
+  assert!(!ISO7816.is_valid(b"YELLOW SUBMARINE\x80\x00\x00\x00", 2));
+  assert!(!ISO10126.is_valid(b"YELLOW SUBMARINE\x00\x00\x00\x05", 4));
+}
+
+#[cfg(test)]
+mod qctests {
+  use super::*;
+  use quickcheck::TestResult;
+  quickcheck! {
+    fn round_trips(data: Vec<u8>, block_size: u8) -> TestResult {
+      if block_size == 0 {
+        return TestResult::discard();
+      }
+      let block_size = block_size as usize;
+      let schemes: [&dyn Padding; 5] = [&PKCS7, &ANSIX923, &ISO10126, &ISO7816, &SSLv3];
+      for scheme in schemes.iter() {
+        let padded = scheme.pad(&data, block_size);
+        if padded.len() % block_size != 0 || scheme.unpad(&padded, block_size) != Some(data.clone()) {
+          return TestResult::failed();
+        }
+      }
+      let padded = Zero.pad(&data, block_size);
+      let is_lossless = data.last() != Some(&0);
+      TestResult::from_bool(!is_lossless || Zero.unpad(&padded, block_size) == Some(data))
+    }
+  }
+}
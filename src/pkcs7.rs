@@ -46,6 +46,30 @@ pub fn unpad_mut(data: &mut Vec<u8>, block_size: usize) {
   data.resize(new_length, 0);
 }
 
+// Whether data ends in well formed padding, without panicking when it
+// doesn't.
+pub fn is_valid(data: &[u8], block_size: usize) -> bool {
+  if data.is_empty() || !data.len().is_multiple_of(block_size) {
+    return false;
+  }
+  let bytes_to_trim = data[data.len() - 1] as usize;
+  bytes_to_trim >= 1
+    && bytes_to_trim <= block_size
+    && data[data.len() - bytes_to_trim..]
+      .iter()
+      .all(|&byte| byte as usize == bytes_to_trim)
+}
+
+#[test]
+fn test_is_valid() {
+  assert!(is_valid(b"ICE ICE BABY\x04\x04\x04\x04", 16));
+  assert!(!is_valid(b"ICE ICE BABY\x05\x05\x05\x05", 16));
+  assert!(!is_valid(b"ICE ICE BABY\x01\x02\x03\x04", 16));
+  assert!(!is_valid(b"ICE ICE BABY\x04\x04\x04\x00", 16));
+  assert!(!is_valid(b"ICE ICE BABY\x04\x04\x04", 16));
+  assert!(!is_valid(b"", 16));
+}
+
 #[cfg(test)]
 mod qctests {
   use quickcheck::TestResult;
@@ -0,0 +1,384 @@
+use gf128::Element;
+use rand::prelude::*;
+use std::ops::{Add, Mul};
+
+// A polynomial with coefficients in GF(2^128), stored lowest degree first and
+// without trailing zero coefficients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Polynomial {
+  coefficients: Vec<Element>,
+}
+
+impl Polynomial {
+  pub fn new(mut coefficients: Vec<Element>) -> Polynomial {
+    while coefficients.last().is_some_and(|c| c.is_zero()) {
+      coefficients.pop();
+    }
+    Polynomial { coefficients }
+  }
+
+  pub fn zero() -> Polynomial {
+    Polynomial::new(vec![])
+  }
+
+  pub fn one() -> Polynomial {
+    Polynomial::constant(Element::one())
+  }
+
+  pub fn constant(c: Element) -> Polynomial {
+    Polynomial::new(vec![c])
+  }
+
+  pub fn x() -> Polynomial {
+    Polynomial::new(vec![Element::zero(), Element::one()])
+  }
+
+  pub fn random<R: Rng>(rng: &mut R, degree: usize) -> Polynomial {
+    Polynomial::new((0..degree + 1).map(|_| Element::random(rng)).collect())
+  }
+
+  pub fn coefficients(&self) -> &[Element] {
+    &self.coefficients
+  }
+
+  pub fn is_zero(&self) -> bool {
+    self.coefficients.is_empty()
+  }
+
+  pub fn is_one(&self) -> bool {
+    self.coefficients == [Element::one()]
+  }
+
+  // The zero polynomial has no degree.
+  pub fn degree(&self) -> Option<usize> {
+    self.coefficients.len().checked_sub(1)
+  }
+
+  pub fn leading_coefficient(&self) -> Element {
+    self
+      .coefficients
+      .last()
+      .cloned()
+      .unwrap_or_else(Element::zero)
+  }
+
+  pub fn scale(&self, c: Element) -> Polynomial {
+    Polynomial::new(self.coefficients.iter().map(|&a| a * c).collect())
+  }
+
+  pub fn monic(&self) -> Polynomial {
+    self.scale(self.leading_coefficient().inverse())
+  }
+
+  pub fn evaluate(&self, x: Element) -> Element {
+    self
+      .coefficients
+      .iter()
+      .rev()
+      .fold(Element::zero(), |accum, &c| accum * x + c)
+  }
+
+  pub fn derivative(&self) -> Polynomial {
+    // Even powers vanish in characteristic 2.
+    Polynomial::new(
+      self
+        .coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &c)| if i % 2 == 1 { c } else { Element::zero() })
+        .collect(),
+    )
+  }
+
+  pub fn divrem(&self, divisor: &Polynomial) -> (Polynomial, Polynomial) {
+    let divisor_degree = divisor.degree().expect("Division by zero polynomial");
+    let mut remainder = self.coefficients.clone();
+    if remainder.len() <= divisor_degree {
+      return (Polynomial::zero(), self.clone());
+    }
+    let inverse_lead = divisor.leading_coefficient().inverse();
+    let mut quotient = vec![Element::zero(); remainder.len() - divisor_degree];
+    for i in (0..quotient.len()).rev() {
+      let factor = remainder[i + divisor_degree] * inverse_lead;
+      if factor.is_zero() {
+        continue;
+      }
+      quotient[i] = factor;
+      for (j, &c) in divisor.coefficients.iter().enumerate() {
+        remainder[i + j] += factor * c;
+      }
+    }
+    remainder.truncate(divisor_degree);
+    (Polynomial::new(quotient), Polynomial::new(remainder))
+  }
+
+  pub fn rem(&self, divisor: &Polynomial) -> Polynomial {
+    self.divrem(divisor).1
+  }
+
+  // The monic greatest common divisor.
+  pub fn gcd(&self, other: &Polynomial) -> Polynomial {
+    let mut a = self.clone();
+    let mut b = other.clone();
+    while !b.is_zero() {
+      let r = a.rem(&b);
+      a = b;
+      b = r;
+    }
+    if a.is_zero() {
+      a
+    } else {
+      a.monic()
+    }
+  }
+
+  pub fn pow_mod(&self, mut exponent: u64, modulus: &Polynomial) -> Polynomial {
+    let mut base = self.rem(modulus);
+    let mut result = Polynomial::one().rem(modulus);
+    while exponent > 0 {
+      if exponent & 1 == 1 {
+        result = (&result * &base).rem(modulus);
+      }
+      base = (&base * &base).rem(modulus);
+      exponent >>= 1;
+    }
+    result
+  }
+
+  // Raises to the power 2^n modulo the modulus by repeated squaring.
+  pub fn square_n_mod(&self, n: usize, modulus: &Polynomial) -> Polynomial {
+    let mut result = self.rem(modulus);
+    for _ in 0..n {
+      result = (&result * &result).rem(modulus);
+    }
+    result
+  }
+
+  // The unique g with g^2 = self, assuming self only has even powers.
+  fn sqrt(&self) -> Polynomial {
+    Polynomial::new(
+      self
+        .coefficients
+        .iter()
+        .step_by(2)
+        .map(|c| c.sqrt())
+        .collect(),
+    )
+  }
+
+  // Splits a monic polynomial into square-free factors paired with their
+  // multiplicities.
+  pub fn square_free_factorization(&self) -> Vec<(Polynomial, usize)> {
+    let mut factors = Vec::new();
+    let f = self.monic();
+    let mut c = f.gcd(&f.derivative());
+    let mut w = f.divrem(&c).0;
+    let mut i = 1;
+    while !w.is_one() {
+      let y = w.gcd(&c);
+      let factor = w.divrem(&y).0;
+      if !factor.is_one() {
+        factors.push((factor, i));
+      }
+      w = y;
+      c = c.divrem(&w).0;
+      i += 1;
+    }
+    if !c.is_one() {
+      for (factor, multiplicity) in c.sqrt().square_free_factorization() {
+        factors.push((factor, multiplicity * 2));
+      }
+    }
+    factors
+  }
+
+  // Splits a monic square-free polynomial into products of irreducible factors
+  // which all share the same degree.
+  pub fn distinct_degree_factorization(&self) -> Vec<(Polynomial, usize)> {
+    let mut factors = Vec::new();
+    let mut f = self.monic();
+    let mut h = Polynomial::x();
+    let mut degree = 1;
+    while f.degree().unwrap() >= 2 * degree {
+      // h = x^(q^degree) mod f, where q = 2^128
+      h = h.square_n_mod(128, &f);
+      let g = f.gcd(&(&h + &Polynomial::x()));
+      if !g.is_one() {
+        f = f.divrem(&g).0;
+        h = h.rem(&f);
+        factors.push((g, degree));
+      }
+      degree += 1;
+    }
+    if f.degree().unwrap() > 0 {
+      let degree = f.degree().unwrap();
+      factors.push((f, degree));
+    }
+    factors
+  }
+
+  // Cantor-Zassenhaus: splits a monic polynomial that is a product of distinct
+  // irreducible factors of the given degree. In characteristic 2 the splitting
+  // polynomial is the trace a + a^2 + ... + a^(2^(128d - 1)) of a random a.
+  pub fn equal_degree_factorization(&self, degree: usize) -> Vec<Polynomial> {
+    let f = self.monic();
+    let n = f.degree().unwrap();
+    if n == degree {
+      return vec![f];
+    }
+    let mut rng = thread_rng();
+    loop {
+      let a = Polynomial::random(&mut rng, n - 1);
+      let mut term = a.rem(&f);
+      let mut trace = term.clone();
+      for _ in 1..128 * degree {
+        term = (&term * &term).rem(&f);
+        trace = &trace + &term;
+      }
+      let g = f.gcd(&trace);
+      if g.is_one() || g.degree() == f.degree() {
+        continue;
+      }
+      let h = f.divrem(&g).0;
+      let mut factors = g.equal_degree_factorization(degree);
+      factors.extend(h.equal_degree_factorization(degree));
+      return factors;
+    }
+  }
+
+  // Monic irreducible factors with their multiplicities.
+  pub fn factor(&self) -> Vec<(Polynomial, usize)> {
+    let mut factors = Vec::new();
+    for (square_free, multiplicity) in self.square_free_factorization() {
+      for (product, degree) in square_free.distinct_degree_factorization() {
+        for factor in product.equal_degree_factorization(degree) {
+          factors.push((factor, multiplicity));
+        }
+      }
+    }
+    factors
+  }
+
+  pub fn roots(&self) -> Vec<Element> {
+    let mut roots = Vec::new();
+    for (square_free, _) in self.square_free_factorization() {
+      // Only the linear factors are of interest: gcd(f, x^q - x)
+      let x = Polynomial::x();
+      let linear = square_free.gcd(&(&x.square_n_mod(128, &square_free) + &x));
+      if linear.degree().is_none_or(|d| d == 0) {
+        continue;
+      }
+      for factor in linear.equal_degree_factorization(1) {
+        roots.push(factor.coefficients[0]);
+      }
+    }
+    roots
+  }
+}
+
+impl Add for &Polynomial {
+  type Output = Polynomial;
+  fn add(self, other: &Polynomial) -> Polynomial {
+    let len = self.coefficients.len().max(other.coefficients.len());
+    let zero = Element::zero();
+    Polynomial::new(
+      (0..len)
+        .map(|i| {
+          *self.coefficients.get(i).unwrap_or(&zero) + *other.coefficients.get(i).unwrap_or(&zero)
+        })
+        .collect(),
+    )
+  }
+}
+
+impl Mul for &Polynomial {
+  type Output = Polynomial;
+  fn mul(self, other: &Polynomial) -> Polynomial {
+    if self.is_zero() || other.is_zero() {
+      return Polynomial::zero();
+    }
+    let mut coefficients =
+      vec![Element::zero(); self.coefficients.len() + other.coefficients.len() - 1];
+    for (i, &a) in self.coefficients.iter().enumerate() {
+      for (j, &b) in other.coefficients.iter().enumerate() {
+        coefficients[i + j] += a * b;
+      }
+    }
+    Polynomial::new(coefficients)
+  }
+}
+
+#[cfg(test)]
+fn linear(root: Element) -> Polynomial {
+  Polynomial::new(vec![root, Element::one()])
+}
+
+#[test]
+fn test_divrem() {
+  let mut rng = thread_rng();
+  let a = Polynomial::random(&mut rng, 7);
+  let b = Polynomial::random(&mut rng, 3);
+  let (q, r) = a.divrem(&b);
+  assert!(r.degree().unwrap() < 3);
+  assert_eq!(a, &(&q * &b) + &r);
+}
+
+#[test]
+fn test_evaluate() {
+  let mut rng = thread_rng();
+  let root = Element::random(&mut rng);
+  let f = &linear(root) * &Polynomial::random(&mut rng, 3);
+  assert!(f.evaluate(root).is_zero());
+}
+
+#[test]
+fn test_square_free_factorization() {
+  let mut rng = thread_rng();
+  let a = linear(Element::random(&mut rng));
+  let b = linear(Element::random(&mut rng));
+  let f = &(&a * &a) * &(&(&b * &b) * &b);
+  let mut factors = f.square_free_factorization();
+  factors.sort_by_key(|&(_, multiplicity)| multiplicity);
+  assert_eq!(vec![(a, 2), (b, 3)], factors);
+}
+
+#[test]
+fn test_roots() {
+  let mut rng = thread_rng();
+  let mut expected: Vec<Element> = (0..3).map(|_| Element::random(&mut rng)).collect();
+  let mut f = Polynomial::random(&mut rng, 2);
+  for &root in expected.iter() {
+    f = &f * &linear(root);
+  }
+  f = &f * &linear(expected[0]);
+  let mut roots = f.roots();
+  let key = |e: &Element| e.to_bytes();
+  roots.sort_by_key(&key);
+  roots.dedup();
+  expected.sort_by_key(&key);
+  // The random quadratic may contribute roots of its own.
+  for root in expected.iter() {
+    assert!(roots.contains(root));
+  }
+  for root in roots.iter() {
+    assert!(f.evaluate(*root).is_zero());
+  }
+}
+
+#[test]
+fn test_factor() {
+  let mut rng = thread_rng();
+  let f = Polynomial::random(&mut rng, 6).monic();
+  let factors = f.factor();
+  let product = factors
+    .iter()
+    .fold(Polynomial::one(), |accum, &(ref factor, multiplicity)| {
+      (0..multiplicity).fold(accum, |accum, _| &accum * factor)
+    });
+  assert_eq!(f, product);
+  for (factor, _) in factors.iter() {
+    assert_eq!(Element::one(), factor.leading_coefficient());
+    assert_eq!(1, factor.factor().len());
+  }
+}
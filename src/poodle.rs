@@ -0,0 +1,67 @@
+use oracle::SSLv3;
+
+// The record grows by a block when the padding wraps around from a single
+// byte to a whole block, so the body length where that happens is the one
+// that makes the last block all padding.
+fn full_padding_body_length<F: Fn(&[u8], &[u8]) -> Vec<u8>>(request: &F) -> usize {
+  let length = |body_length| request(b"", &vec![b'A'; body_length]).len();
+  (1..=16).find(|&n| length(n) > length(n - 1)).unwrap()
+}
+
+// Recovers the bytes of a request from the given offset on, where offsets
+// count from the start of a request with an empty path. Each byte is moved
+// to the end of a block with the path, while the body keeps the last block
+// all padding. That block is then swapped for the one holding the byte,
+// which the server only accepts when its last byte decrypts to 15, and a
+// fresh IV makes every request another try.
+fn recover<F: Fn(&[u8], &[u8]) -> Vec<u8>>(
+  request: &F,
+  server: &SSLv3,
+  offset: usize,
+  length: usize,
+) -> Vec<u8> {
+  let full_padding = full_padding_body_length(request);
+  (offset..offset + length)
+    .map(|target| {
+      let path = vec![b'A'; 15 - target % 16];
+      let body = vec![b'A'; (full_padding + 16 - path.len()) % 16];
+      let block = (target + path.len()) / 16 + 1;
+      loop {
+        let mut record = request(&path, &body);
+        let last = record.len() - 16;
+        let previous = record[16 * block - 1];
+        let last_previous = record[last - 1];
+        record.copy_within(16 * block..16 * (block + 1), last);
+        if server.open(&record).is_ok() {
+          return 15 ^ last_previous ^ previous;
+        }
+      }
+    })
+    .collect()
+}
+
+#[test]
+fn poodle() {
+  use oracle::{ConstantAppend, ConstantPrepend, Oracle};
+  let server = SSLv3::new();
+  let cookie = b"session=9f86d081884c7d659a2feaa0";
+
+  // The victim's browser can be made to send requests with any path and
+  // body, and adds its cookie in between.
+  let request = |path: &[u8], body: &[u8]| {
+    let mut suffix = b" HTTP/1.1\r\nCookie: ".to_vec();
+    suffix.extend_from_slice(cookie);
+    suffix.extend_from_slice(b"\r\n\r\n");
+    suffix.extend_from_slice(body);
+    ConstantAppend::new(ConstantPrepend::new(&server, b"POST /".to_vec()), suffix)
+      .encode(path)
+      .data
+  };
+  assert!(server.open(&request(b"index.html", b"")).is_ok());
+
+  let offset = b"POST / HTTP/1.1\r\nCookie: ".len();
+  assert_eq!(
+    cookie.to_vec(),
+    recover(&request, &server, offset, cookie.len())
+  );
+}
@@ -0,0 +1,80 @@
+pub struct RC4 {
+  state: [u8; 256],
+  i: u8,
+  j: u8,
+}
+
+impl RC4 {
+  pub fn new(key: &[u8]) -> RC4 {
+    assert!(
+      !key.is_empty() && key.len() <= 256,
+      "Unsupported key length {}",
+      key.len()
+    );
+    let mut state = [0; 256];
+    for (i, e) in state.iter_mut().enumerate() {
+      *e = i as u8;
+    }
+    let mut j = 0u8;
+    for i in 0..256 {
+      j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+      state.swap(i, j as usize);
+    }
+    RC4 { state, i: 0, j: 0 }
+  }
+
+  pub fn next_byte(&mut self) -> u8 {
+    self.i = self.i.wrapping_add(1);
+    self.j = self.j.wrapping_add(self.state[self.i as usize]);
+    self.state.swap(self.i as usize, self.j as usize);
+    let index = self.state[self.i as usize].wrapping_add(self.state[self.j as usize]);
+    self.state[index as usize]
+  }
+
+  pub fn apply_mut(&mut self, data: &mut [u8]) {
+    for e in data.iter_mut() {
+      *e ^= self.next_byte();
+    }
+  }
+}
+
+pub fn perform(data: &[u8], key: &[u8]) -> Vec<u8> {
+  let mut v = data.to_vec();
+  RC4::new(key).apply_mut(&mut v);
+  v
+}
+
+#[test]
+fn test_vectors() {
+  use util::parse_byte_string;
+  assert_eq!(
+    parse_byte_string("bbf316e8d940af0ad3"),
+    perform(b"Plaintext", b"Key")
+  );
+  assert_eq!(parse_byte_string("1021bf0420"), perform(b"pedia", b"Wiki"));
+  assert_eq!(
+    parse_byte_string("45a01f645fc35b383552544b9bf5"),
+    perform(b"Attack at dawn", b"Secret")
+  );
+}
+
+#[test]
+fn keystream() {
+  use util::parse_byte_string;
+  let mut rc4 = RC4::new(b"Key");
+  let keystream: Vec<u8> = (0..10).map(|_| rc4.next_byte()).collect();
+  assert_eq!(parse_byte_string("eb9f7781b734ca72a719"), keystream);
+}
+
+#[cfg(test)]
+mod qctests {
+  use quickcheck::TestResult;
+  quickcheck! {
+    fn bijection(data: Vec<u8>, key: Vec<u8>) -> TestResult {
+      if key.is_empty() || key.len() > 256 {
+        return TestResult::discard();
+      }
+      TestResult::from_bool(super::perform(&super::perform(&data, &key), &key) == data)
+    }
+  }
+}
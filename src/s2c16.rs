@@ -58,3 +58,118 @@ fn challenge() {
   }
   assert_eq!(true, validate_ciphertext(&modified_ciphertext));
 }
+
+// The same attack against the other modes, with the flips made wherever the
+// mode carries them into the plaintext.
+#[cfg(test)]
+mod other_modes {
+  use aes::CipherMode;
+  use oracle::{self, DecodableOracle, Oracle};
+  use std::panic;
+
+  const MAGIC_STRING: &str = ";admin=true;";
+
+  fn make_oracle(cipher_mode: CipherMode) -> impl Oracle + DecodableOracle {
+    oracle::QuoteBytes::new(
+      oracle::ConstantAppend::new(
+        oracle::ConstantPrepend::new(
+          oracle::AES128::with_mode(cipher_mode),
+          "comment1=cooking%20MCs;userdata=".as_bytes().to_vec(),
+        ),
+        ";comment2=%20like%20a%20pound%20of%20bacon"
+          .as_bytes()
+          .to_vec(),
+      ),
+      b";&",
+    )
+  }
+
+  fn is_admin<O: DecodableOracle>(o: &O, ciphertext: &[u8]) -> bool {
+    String::from_utf8_lossy(&o.decode(ciphertext)).contains(MAGIC_STRING)
+  }
+
+  // The magic string comes back quoted as "%3badmin=true%3b", so turning the
+  // first 'b' and the last '%' into semicolons brings it back. Each of those
+  // bytes is flipped through the ciphertext byte the given distance before
+  // it. Returns the plaintext before the flips and the flipped ciphertext.
+  fn forge<O: Oracle + DecodableOracle>(o: &O, distance: usize) -> (Vec<u8>, Vec<u8>) {
+    let ciphertext = o.encode(MAGIC_STRING.as_bytes()).data;
+    let initial_plaintext = o.decode(&ciphertext);
+    let insertion_index = initial_plaintext.iter().position(|&b| b == b'b').unwrap();
+    let mut modified_ciphertext = ciphertext.clone();
+    for &i in [0, MAGIC_STRING.len() - 1].iter() {
+      let target_index = insertion_index + i;
+      modified_ciphertext[target_index - distance] ^= initial_plaintext[target_index] ^ b';';
+    }
+    (initial_plaintext, modified_ciphertext)
+  }
+
+  #[test]
+  fn cfb() {
+    let o = make_oracle(CipherMode::CFB([7; 16]));
+    // CFB flips the same bit of the plaintext and scrambles the block after
+    // it, which here only holds the suffix.
+    let (initial_plaintext, modified_ciphertext) = forge(&o, 0);
+    assert!(is_admin(&o, &modified_ciphertext));
+    let plaintext = o.decode(&modified_ciphertext);
+    assert_eq!(initial_plaintext[..32], plaintext[..32]);
+    assert_ne!(initial_plaintext[48..64], plaintext[48..64]);
+  }
+
+  #[test]
+  fn ofb() {
+    let o = make_oracle(CipherMode::OFB([7; 16]));
+    // OFB is a plain stream cipher: the flips touch nothing else.
+    let (initial_plaintext, modified_ciphertext) = forge(&o, 0);
+    assert!(is_admin(&o, &modified_ciphertext));
+    let plaintext = o.decode(&modified_ciphertext);
+    let changed = initial_plaintext
+      .iter()
+      .zip(plaintext.iter())
+      .filter(|&(a, b)| a != b)
+      .count();
+    assert_eq!(2, changed);
+  }
+
+  #[test]
+  fn cfb8() {
+    let o = make_oracle(CipherMode::CFB8([7; 16]));
+    // A flipped byte stays in the shift register for the next 16 bytes and
+    // scrambles all of them, so two flips less than a block apart can't both
+    // land.
+    let (initial_plaintext, modified_ciphertext) = forge(&o, 0);
+    assert!(!is_admin(&o, &modified_ciphertext));
+
+    let ciphertext = o.encode(MAGIC_STRING.as_bytes()).data;
+    let index = initial_plaintext.iter().position(|&b| b == b'b').unwrap();
+    let mut modified_ciphertext = ciphertext.clone();
+    modified_ciphertext[index] ^= b'b' ^ b';';
+    let plaintext = o.decode(&modified_ciphertext);
+    assert_eq!(b';', plaintext[index]);
+    assert_ne!(
+      initial_plaintext[index + 1..index + 17],
+      plaintext[index + 1..index + 17]
+    );
+    assert_eq!(initial_plaintext[index + 17..], plaintext[index + 17..]);
+  }
+
+  #[test]
+  fn pcbc() {
+    let o = make_oracle(CipherMode::PCBC([7; 16]));
+    // The CBC flips scramble the block they're made in, and that block feeds
+    // into the chaining value of every block after it, so the attack takes
+    // the rest of the message with it, padding included.
+    let (_, modified_ciphertext) = forge(&o, 16);
+    assert!(panic::catch_unwind(|| o.decode(&modified_ciphertext)).is_err());
+
+    // Swapping two adjacent blocks only scrambles those two, since their
+    // combined contribution to the chaining value stays the same.
+    let mut swapped_ciphertext = o.encode(MAGIC_STRING.as_bytes()).data;
+    let initial_plaintext = o.decode(&swapped_ciphertext);
+    swapped_ciphertext[32..64].rotate_left(16);
+    let plaintext = o.decode(&swapped_ciphertext);
+    assert_eq!(initial_plaintext[..32], plaintext[..32]);
+    assert_ne!(initial_plaintext[32..64], plaintext[32..64]);
+    assert_eq!(initial_plaintext[64..], plaintext[64..]);
+  }
+}
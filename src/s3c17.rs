@@ -0,0 +1,142 @@
+use oracle::{CBCPadding, Oracle};
+use padding::{self, Padding};
+
+const STRINGS: [&str; 10] = [
+  "MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc=",
+  "MDAwMDAxV2l0aCB0aGUgYmFzcyBraWNrZWQgaW4gYW5kIHRoZSBWZWdhJ3MgYXJlIHB1bXBpbic=",
+  "MDAwMDAyUXVpY2sgdG8gdGhlIHBvaW50LCB0byB0aGUgcG9pbnQsIG5vIGZha2luZw==",
+  "MDAwMDAzQ29va2luZyBNQydzIGxpa2UgYSBwb3VuZCBvZiBiYWNvbg==",
+  "MDAwMDA0QnVybmluZyAnZW0sIGlmIHlvdSBhaW4ndCBxdWljayBhbmQgbmltYmxl",
+  "MDAwMDA1SSBnbyBjcmF6eSB3aGVuIEkgaGVhciBhIGN5bWJhbA==",
+  "MDAwMDA2QW5kIGEgaGlnaCBoYXQgd2l0aCBhIHNvdXBlZCB1cCB0ZW1wbw==",
+  "MDAwMDA3SSdtIG9uIGEgcm9sbCwgaXQncyB0aW1lIHRvIGdvIHNvbG8=",
+  "MDAwMDA4b2xsaW4nIGluIG15IGZpdmUgcG9pbnQgb2g=",
+  "MDAwMDA5aXRoIG15IHJhZy10b3AgZG93biBzbyBteSBoYWlyIGNhbiBibG93",
+];
+
+// Finds the bytes of a block's decryption from the end backwards: with the
+// ones after it known, a forged previous block can make them into the suffix
+// the scheme forces for that padding length, and only the right guess for
+// the next byte makes the oracle accept it. Returns the end of the plaintext
+// as far back as the scheme has such suffixes.
+fn decrypt_block<P: Padding>(
+  o: &CBCPadding<P>,
+  padding: &P,
+  previous: &[u8],
+  block: &[u8],
+) -> Vec<u8> {
+  let is_valid = |forged: &[u8]| {
+    let mut ciphertext = forged.to_vec();
+    ciphertext.extend_from_slice(block);
+    o.is_padding_valid(&ciphertext)
+  };
+  let mut intermediate = [0; 16];
+  let mut known = 0;
+  while known < 16 {
+    let position = 15 - known;
+    let suffix = match padding.forced_suffix(known + 1, 16) {
+      Some(suffix) => suffix,
+      None => break,
+    };
+    let mut forged = [0; 16];
+    for i in position + 1..16 {
+      forged[i] = intermediate[i] ^ suffix[i - position];
+    }
+    // A guess that only works as part of longer padding stops working once
+    // the byte before it changes.
+    let guess = (0..=255).find(|&guess| {
+      forged[position] = guess;
+      if !is_valid(&forged) {
+        return false;
+      }
+      position == 0 || {
+        let mut changed = forged;
+        changed[position - 1] ^= 1;
+        is_valid(&changed)
+      }
+    });
+    match guess {
+      Some(guess) => intermediate[position] = guess ^ suffix[0],
+      None => break,
+    }
+    known += 1;
+  }
+  (16 - known..16)
+    .map(|i| intermediate[i] ^ previous[i])
+    .collect()
+}
+
+fn decrypt<P: Padding>(o: &CBCPadding<P>, padding: &P, ciphertext: &[u8]) -> Vec<u8> {
+  ciphertext
+    .windows(32)
+    .step_by(16)
+    .flat_map(|blocks| decrypt_block(o, padding, &blocks[..16], &blocks[16..]))
+    .collect()
+}
+
+#[test]
+fn challenge() {
+  use b64;
+  let o = CBCPadding::new(padding::PKCS7);
+  for s in STRINGS.iter() {
+    let plaintext = b64::decode(s);
+    let ciphertext = o.encode(&plaintext).data;
+    let decrypted = decrypt(&o, &padding::PKCS7, &ciphertext);
+    assert_eq!(Some(plaintext), padding::PKCS7.unpad(&decrypted, 16));
+  }
+}
+
+#[test]
+fn other_padding_schemes() {
+  use oracle::DecodableOracle;
+  let plaintext = b"Cooking MC's like a pound of bacon";
+
+  // Any scheme whose padding fixes every byte it covers falls the same way.
+  fn check<P: Padding + Copy>(padding: P, plaintext: &[u8]) {
+    let o = CBCPadding::new(padding);
+    let ciphertext = o.encode(plaintext).data;
+    assert_eq!(plaintext.to_vec(), o.decode(&ciphertext));
+    assert_eq!(
+      o.decode(&ciphertext),
+      padding
+        .unpad(&decrypt(&o, &padding, &ciphertext), 16)
+        .unwrap()
+    );
+  }
+  check(padding::ANSIX923, plaintext);
+  check(padding::ISO7816, plaintext);
+
+  // ISO 10126 only checks that the last byte is a possible length, which
+  // sixteen values are, so the suffixes run out straight away. The set of
+  // last bytes the oracle accepts still gives away the last byte though.
+  let o = CBCPadding::new(padding::ISO10126);
+  let ciphertext = o.encode(plaintext).data;
+  assert!(decrypt(&o, &padding::ISO10126, &ciphertext).is_empty());
+  let accepted = (0..=255)
+    .filter(|&guess| {
+      let mut forged = [0; 32];
+      forged[15] = guess;
+      forged[16..].copy_from_slice(&ciphertext[16..32]);
+      o.is_padding_valid(&forged)
+    })
+    .collect::<Vec<u8>>();
+  assert_eq!(16, accepted.len());
+  let intermediate = (0..=255)
+    .find(|&candidate| {
+      accepted
+        .iter()
+        .all(|&guess| (1..=16).contains(&(guess ^ candidate)))
+    })
+    .unwrap();
+  assert_eq!(plaintext[15], intermediate ^ ciphertext[15]);
+
+  // Zero padding accepts everything, so the oracle says nothing.
+  let o = CBCPadding::new(padding::Zero);
+  let ciphertext = o.encode(plaintext).data;
+  assert!(decrypt(&o, &padding::Zero, &ciphertext).is_empty());
+  assert!((0..=255).all(|guess| {
+    let mut forged = ciphertext.clone();
+    forged[15] ^= guess;
+    o.is_padding_valid(&forged)
+  }));
+}
@@ -0,0 +1,52 @@
+#[test]
+fn challenge() {
+  use md;
+  use std::collections::HashMap;
+
+  // f is cheap with a 16 bit state, g is "expensive" with a 24 bit state.
+  let f = md::MDHash::new(2);
+  let g = md::MDHash::with_initial_state(vec![0x5a, 0xa5, 0x3c]);
+
+  // Hash every message of the multicollision under g, sharing the work for
+  // common prefixes. Returns the (message index, g hash) pairs.
+  let g_hashes = |multicollision: &md::Multicollision| -> Vec<(usize, Vec<u8>)> {
+    let mut states = vec![(0usize, g.initial_state().to_vec())];
+    for (i, collision) in multicollision.collisions().iter().enumerate() {
+      let mut next_states = Vec::with_capacity(states.len() * 2);
+      for (index, state) in states.iter() {
+        next_states.push((*index, g.compress(state, &collision.block_a)));
+        next_states.push((index | (1 << i), g.compress(state, &collision.block_b)));
+      }
+      states = next_states;
+    }
+    let message_len = multicollision.collisions().len() * md::BLOCK_SIZE;
+    let padding = md::pad(&vec![0; message_len]).split_off(message_len);
+    states
+      .into_iter()
+      .map(|(index, state)| (index, g.hash_blocks(&state, &padding)))
+      .collect()
+  };
+
+  // We expect a collision in g after about 2^(24/2) messages.
+  let mut multicollision = f.multicollision(f.initial_state(), 12);
+  let (message_a, message_b) = loop {
+    println!(
+      "Searching {} messages for a collision in g",
+      multicollision.message_count()
+    );
+    let mut seen = HashMap::new();
+    let found = g_hashes(&multicollision)
+      .into_iter()
+      .find_map(|(index, hash)| seen.insert(hash, index).map(|other| (other, index)));
+    match found {
+      Some((a, b)) => break (multicollision.message(a), multicollision.message(b)),
+      None => multicollision.extend(&f),
+    }
+  };
+
+  assert_ne!(message_a, message_b);
+  assert_eq!(f.hash(&message_a), f.hash(&message_b));
+  assert_eq!(g.hash(&message_a), g.hash(&message_b));
+  let h = |message: &[u8]| [f.hash(message), g.hash(message)].concat();
+  assert_eq!(h(&message_a), h(&message_b));
+}
@@ -0,0 +1,44 @@
+#[test]
+fn challenge() {
+  use md;
+  use rand::thread_rng;
+  use std::collections::HashMap;
+  use util;
+
+  const K: usize = 8;
+  let h = md::MDHash::new(2);
+
+  let original = util::gen_random_bytes(&mut thread_rng(), (1 << K) * md::BLOCK_SIZE);
+  let original_hash = h.hash(&original);
+
+  // Record the intermediate states of the original message. Only those that
+  // can be reached with a message the expandable message can produce are
+  // useful: we need at least K blocks before the bridge block.
+  let mut intermediate_states = HashMap::new();
+  let mut state = h.initial_state().to_vec();
+  for (i, block) in original.chunks(md::BLOCK_SIZE).enumerate() {
+    state = h.compress(&state, block);
+    if i >= K {
+      intermediate_states.insert(state.clone(), i);
+    }
+  }
+
+  let expandable = md::ExpandableMessage::new(&h, h.initial_state(), K);
+  let mut rng = thread_rng();
+  let (bridge, bridge_index) = loop {
+    let block = util::gen_random_bytes(&mut rng, md::BLOCK_SIZE);
+    let state = h.compress(expandable.state(), &block);
+    if let Some(&index) = intermediate_states.get(&state) {
+      break (block, index);
+    }
+  };
+  println!("Bridge block lands on block {}", bridge_index);
+
+  let mut second_preimage = expandable.message(bridge_index);
+  second_preimage.extend(bridge);
+  second_preimage.extend(&original[(bridge_index + 1) * md::BLOCK_SIZE..]);
+
+  assert_eq!(original.len(), second_preimage.len());
+  assert_ne!(original, second_preimage);
+  assert_eq!(original_hash, h.hash(&second_preimage));
+}
@@ -0,0 +1,36 @@
+#[test]
+fn challenge() {
+  use md;
+  use rand::thread_rng;
+  use util;
+
+  const K: usize = 5;
+  const PREDICTION_BLOCKS: usize = 4;
+  let h = md::MDHash::new(2);
+
+  // Build the diamond ahead of time and commit to a hash for a message of a
+  // known length: the prediction, a linking block and the path to the root.
+  let diamond = md::DiamondStructure::new(&h, K);
+  let message_len = (PREDICTION_BLOCKS + 1 + diamond.depth()) * md::BLOCK_SIZE;
+  let padding = md::pad(&vec![0; message_len]).split_off(message_len);
+  let committed_hash = h.hash_blocks(diamond.state(), &padding);
+
+  // Once the results are known, herd the real prediction into the diamond.
+  let mut prediction = b"Final scores: Mariners 3, Yankees 2, Red Sox 7, Blue Jays 5.".to_vec();
+  prediction.resize(PREDICTION_BLOCKS * md::BLOCK_SIZE, b' ');
+  let prediction_state = h.hash_blocks(h.initial_state(), &prediction);
+  let mut rng = thread_rng();
+  let (link, leaf_index) = loop {
+    let block = util::gen_random_bytes(&mut rng, md::BLOCK_SIZE);
+    if let Some(index) = diamond.leaf_index(&h.compress(&prediction_state, &block)) {
+      break (block, index);
+    }
+  };
+
+  let mut message = prediction.clone();
+  message.extend(link);
+  message.extend(diamond.path_from(leaf_index));
+  assert_eq!(message_len, message.len());
+  assert!(message.starts_with(b"Final scores: Mariners 3"));
+  assert_eq!(committed_hash, h.hash(&message));
+}
@@ -0,0 +1,131 @@
+use oracle;
+
+struct Bias {
+  // Zero based index into the keystream
+  position: usize,
+  value: u8,
+}
+
+// Recovers the bytes of the secret appended by the oracle that a prefix of
+// the given length lines up with the biased keystream positions, picking for
+// each the plaintext that makes the biased value most common. A single batch
+// of encryptions serves every bias. Returns (index into the secret, byte)
+// pairs.
+fn recover_bytes(
+  o: &(dyn oracle::Oracle + Sync),
+  prefix_length: usize,
+  biases: &[Bias],
+  samples: usize,
+) -> Vec<(usize, u8)> {
+  use rayon::prelude::*;
+  let prefix = vec![b'A'; prefix_length];
+  let length = o.encode(&prefix).data.len();
+  let biases: Vec<&Bias> = biases
+    .iter()
+    .filter(|bias| bias.position >= prefix_length && bias.position < length)
+    .collect();
+  let counts = (0..samples)
+    .into_par_iter()
+    .fold(
+      || vec![[0u64; 256]; biases.len()],
+      |mut counts, _| {
+        let ciphertext = o.encode(&prefix).data;
+        for (bias, counts) in biases.iter().zip(counts.iter_mut()) {
+          counts[ciphertext[bias.position] as usize] += 1;
+        }
+        counts
+      },
+    )
+    .reduce(
+      || vec![[0u64; 256]; biases.len()],
+      |mut a, b| {
+        for (a, b) in a.iter_mut().zip(b.iter()) {
+          for (a, b) in a.iter_mut().zip(b.iter()) {
+            *a += b;
+          }
+        }
+        a
+      },
+    );
+  biases
+    .iter()
+    .zip(counts.iter())
+    .map(|(bias, counts)| {
+      let byte = (0..256)
+        .max_by_key(|&p| counts[p ^ bias.value as usize])
+        .unwrap();
+      (bias.position - prefix_length, byte as u8)
+    })
+    .collect()
+}
+
+// Lines each byte of the secret up with one of the biased positions by
+// varying the prefix length, then puts the recovered bytes together.
+fn recover_cookie(o: &(dyn oracle::Oracle + Sync), biases: &[Bias], samples: usize) -> Vec<u8> {
+  let prefix_lengths = biases.iter().map(|bias| bias.position + 1).min().unwrap();
+  let mut cookie = vec![None; o.encode(&[]).data.len()];
+  for prefix_length in 0..prefix_lengths {
+    for (i, byte) in recover_bytes(o, prefix_length, biases, samples) {
+      cookie[i] = Some(byte);
+    }
+  }
+  cookie
+    .into_iter()
+    .map(|byte| byte.expect("No bias lines up with this byte"))
+    .collect()
+}
+
+#[test]
+fn second_byte_bias() {
+  // Mantin and Shamir: the second keystream byte is 0 twice as often as it
+  // should be. This is strong enough to be cheap to demonstrate.
+  let cookie = b"Hi".to_vec();
+  let o = oracle::ConstantAppend::new(oracle::RC4::new(), cookie.clone());
+  let biases = [Bias {
+    position: 1,
+    value: 0,
+  }];
+  assert_eq!(vec![(1, b'i')], recover_bytes(&o, 0, &biases, 1 << 14));
+  assert_eq!(vec![(0, b'H')], recover_bytes(&o, 1, &biases, 1 << 14));
+}
+
+// The 16th keystream byte is 240 and the 32nd 224 about 3.7% and 2.5% more
+// often than they should be, which takes 2^24 encryptions to pick out
+// reliably.
+fn sixteenth_and_thirty_second_byte_biases() -> [Bias; 2] {
+  [
+    Bias {
+      position: 15,
+      value: 240,
+    },
+    Bias {
+      position: 31,
+      value: 224,
+    },
+  ]
+}
+
+#[test]
+fn sixteenth_and_thirty_second_byte_bias() {
+  // One prefix length gives away two bytes sixteen apart.
+  let cookie = b"BE SURE TO DRINK YOUR OVALTINE".to_vec();
+  let o = oracle::ConstantAppend::new(oracle::RC4::new(), cookie.clone());
+  assert_eq!(
+    vec![(13, cookie[13]), (29, cookie[29])],
+    recover_bytes(&o, 2, &sixteenth_and_thirty_second_byte_biases(), 1 << 24)
+  );
+}
+
+// Sixteen rounds of 2^24 encryptions take about ten minutes.
+#[test]
+#[ignore]
+fn challenge() {
+  use b64;
+  let cookie = b64::decode("QkUgU1VSRSBUTyBEUklOSyBZT1VSIE9WQUxUSU5F");
+  assert_eq!(b"BE SURE TO DRINK YOUR OVALTINE".to_vec(), cookie);
+  let o = oracle::ConstantAppend::new(oracle::RC4::new(), cookie.clone());
+  assert_eq!(
+    cookie,
+    recover_cookie(&o, &sixteenth_and_thirty_second_byte_biases(), 1 << 24)
+  );
+}
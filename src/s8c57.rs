@@ -0,0 +1,45 @@
+#[test]
+fn challenge() {
+  use bigint::{self, BigUint};
+  use dlog;
+  use hmac;
+  use rand::thread_rng;
+
+  const MESSAGE: &[u8] = b"crazy flamboyant for the rap enjoyment";
+  // A deliberately weak group: g generates the subgroup of prime order q, but
+  // p - 1 = q * j where j has plenty of small factors.
+  let p = BigUint::from_str_radix("152011477512758499385640758554892241284221132162671", 10);
+  let q = BigUint::from_str_radix("2973359363506532711", 10);
+  let g = BigUint::from_str_radix("99440278093025400440532973112694822433855490590861", 10);
+  let j = BigUint::from_str_radix("51124488811701794106130144303970", 10);
+  assert_eq!(&p - &BigUint::one(), &q * &j);
+  assert!(g.modpow(&q, &p).is_one());
+
+  let mut rng = thread_rng();
+  let bob_private = BigUint::random_below(&mut rng, &q);
+  // Bob never checks that the public key he is sent lies in the subgroup of
+  // order q.
+  let bob = |public_key: &BigUint| -> Vec<u8> {
+    let shared_secret = public_key.modpow(&bob_private, &p);
+    hmac::hmac_sha1(&shared_secret.to_bytes_be(), MESSAGE)
+  };
+
+  // Bob's shared secrets live in the whole group, of order p - 1.
+  let group = dlog::MultiplicativeGroup::new(p.clone());
+  let order = &p - &BigUint::one();
+  let mac = |shared_secret: &BigUint| hmac::hmac_sha1(&shared_secret.to_bytes_be(), MESSAGE);
+  let mut residues = Vec::new();
+  for r in j.small_prime_factors(1 << 16) {
+    // An element of order r confines the shared secret to r possible values.
+    let residue = dlog::confined_residue(&group, &order, r, &mut rng, bob, mac).unwrap();
+    println!("Private key is {} mod {}", residue, r);
+    residues.push((
+      BigUint::from_u64(u64::from(residue)),
+      BigUint::from_u64(u64::from(r)),
+    ));
+  }
+
+  let (recovered, modulus) = bigint::crt(&residues);
+  assert!(modulus > q);
+  assert_eq!(bob_private, recovered);
+}
@@ -0,0 +1,49 @@
+#[test]
+fn challenge() {
+  use bigint::{self, BigUint};
+  use dlog;
+  use hmac;
+  use rand::thread_rng;
+
+  const MESSAGE: &[u8] = b"crazy flamboyant for the rap enjoyment";
+  // Like the group from challenge 57, except that the small factors of j only
+  // cover part of q.
+  let p = BigUint::from_str_radix("600248816845357641611606591664610058728689495299", 10);
+  let q = BigUint::from_str_radix("3344610804643661755603", 10);
+  let g = BigUint::from_str_radix("8096911278510662937651222300084183721755009561", 10);
+  let j = BigUint::from_str_radix("179467463303046036297008566", 10);
+  assert_eq!(&p - &BigUint::one(), &q * &j);
+  assert!(g.modpow(&q, &p).is_one());
+
+  let mut rng = thread_rng();
+  let bob_private = BigUint::random_below(&mut rng, &q);
+  let bob_public = g.modpow(&bob_private, &p);
+  let bob = |public_key: &BigUint| -> Vec<u8> {
+    let shared_secret = public_key.modpow(&bob_private, &p);
+    hmac::hmac_sha1(&shared_secret.to_bytes_be(), MESSAGE)
+  };
+
+  let group = dlog::MultiplicativeGroup::new(p.clone());
+  let order = &p - &BigUint::one();
+  let mac = |shared_secret: &BigUint| hmac::hmac_sha1(&shared_secret.to_bytes_be(), MESSAGE);
+  let mut residues = Vec::new();
+  for r in j.small_prime_factors(1 << 16) {
+    let residue = dlog::confined_residue(&group, &order, r, &mut rng, bob, mac).unwrap();
+    residues.push((
+      BigUint::from_u64(u64::from(residue)),
+      BigUint::from_u64(u64::from(r)),
+    ));
+  }
+  let (n, r) = bigint::crt(&residues);
+  println!("Private key is {} mod {}", n, r);
+
+  // With x = n + m * r, y * g^-n = (g^r)^m and m is at most q / r, which is
+  // small enough for the kangaroo.
+  let g_prime = g.modpow(&r, &p);
+  let y_prime = bob_public.mul_mod(&g.modpow(&n, &p).modinv(&p).unwrap(), &p);
+  let bound = (&q / &r).to_u64().unwrap();
+  let group = dlog::MultiplicativeGroup::new(p.clone());
+  let m = dlog::kangaroo(&group, &g_prime, &y_prime, 0, bound).unwrap();
+  let recovered = &n + &(&BigUint::from_u64(m) * &r);
+  assert_eq!(bob_private, recovered);
+}
@@ -0,0 +1,69 @@
+#[test]
+fn challenge() {
+  use bigint::{self, BigUint};
+  use dlog;
+  use ec;
+  use hmac;
+  use rand::thread_rng;
+  use sha1;
+
+  const MESSAGE: &[u8] = b"crazy flamboyant for the rap enjoyment";
+  let domain = ec::Domain::cryptopals();
+  let mut rng = thread_rng();
+  let (bob_private, _) = domain.generate_keypair(&mut rng);
+
+  let derive_key = |point: &ec::Point| match *point {
+    ec::Point::Infinity => sha1::hash(&[]),
+    ec::Point::Affine(ref x, ref y) => sha1::hash(&[x.to_bytes_be(), y.to_bytes_be()].concat()),
+  };
+  // Bob never checks that the point he is sent is actually on his curve.
+  let bob = |point: &ec::Point| -> Vec<u8> {
+    let shared_secret = domain.shared_secret(&bob_private, point);
+    hmac::hmac_sha1(&derive_key(&shared_secret), MESSAGE)
+  };
+  let mac = |shared_secret: &ec::Point| hmac::hmac_sha1(&derive_key(shared_secret), MESSAGE);
+
+  // Curves sharing a and p but not b use the same addition formulas, and
+  // their orders have plenty of small factors.
+  let invalid_curves = [
+    (210, "233970423115425145550826547352470124412"),
+    (504, "233970423115425145544350131142039591210"),
+    (727, "233970423115425145545378039958152057148"),
+  ];
+  let mut subgroups = Vec::new();
+  for &(b, order) in invalid_curves.iter() {
+    let curve = ec::WeierstrassCurve::new(
+      domain.curve.a.clone(),
+      BigUint::from_u64(b),
+      domain.curve.p.clone(),
+    );
+    let order = BigUint::from_str_radix(order, 10);
+    for r in order.small_prime_factors(1 << 16) {
+      // With r^2 dividing the order, the cofactor can kill every point.
+      let r_squared = BigUint::from_u64(u64::from(r) * u64::from(r));
+      if (&order % &r_squared).is_zero() {
+        continue;
+      }
+      if !subgroups.iter().any(|&(_, other, _)| other == r) {
+        subgroups.push((curve.clone(), r, order.clone()));
+      }
+    }
+  }
+  subgroups.sort_by_key(|&(_, r, _)| r);
+
+  let mut residues = Vec::new();
+  let mut modulus = BigUint::one();
+  for &(ref curve, r, ref order) in subgroups.iter() {
+    if modulus > domain.order {
+      break;
+    }
+    let r_big = BigUint::from_u64(u64::from(r));
+    let residue = dlog::confined_residue(curve, order, r, &mut rng, bob, mac).unwrap();
+    println!("Private key is {} mod {}", residue, r);
+    residues.push((BigUint::from_u64(u64::from(residue)), r_big.clone()));
+    modulus = &modulus * &r_big;
+  }
+
+  let (recovered, _) = bigint::crt(&residues);
+  assert_eq!(bob_private, recovered);
+}
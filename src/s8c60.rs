@@ -0,0 +1,121 @@
+#[test]
+fn challenge() {
+  use bigint::{self, BigUint};
+  use dlog::{self, Group};
+  use ec;
+  use hmac;
+  use rand::thread_rng;
+
+  const MESSAGE: &[u8] = b"crazy flamboyant for the rap enjoyment";
+  let curve = ec::MontgomeryCurve::cryptopals();
+  let domain = ec::Domain::cryptopals();
+  let mut rng = thread_rng();
+  // The twist's small subgroups only pin the key down modulo about 2^28, and
+  // a key drawn from the full group order would leave the kangaroo 2^96
+  // values to search. Bob's key is drawn from below 2^KEY_BITS instead, which
+  // leaves it about 2^20, so this is not a full order recovery.
+  const KEY_BITS: u64 = 48;
+  let key_bound = BigUint::from_u64(1 << KEY_BITS);
+  let bob_private = BigUint::random_below(&mut rng, &key_bound);
+  assert!(bob_private < key_bound);
+  assert!(key_bound < domain.order);
+  let bob_public = curve.ladder(&BigUint::from_u64(4), &bob_private);
+
+  // The ladder only ever sees u, so Bob can't tell that a u coordinate isn't
+  // on his curve but on its twist.
+  let bob = |u: &BigUint| -> Vec<u8> {
+    let shared_secret = curve.ladder(u, &bob_private);
+    hmac::hmac_sha1(&shared_secret.to_bytes_be(), MESSAGE)
+  };
+  let is_match = |u: &BigUint, k: &BigUint, mac: &[u8]| {
+    hmac::hmac_sha1(&curve.ladder(u, k).to_bytes_be(), MESSAGE) == mac
+  };
+
+  let p = &curve.p;
+  let curve_order = &domain.order * &BigUint::from_u64(8);
+  let twist_order = &(&(p * &BigUint::from_u64(2)) + &BigUint::from_u64(2)) - &curve_order;
+  // The twist's other factors are too large to brute force here.
+  let factors: Vec<u32> = twist_order
+    .small_prime_factors(1 << 11)
+    .into_iter()
+    .filter(|&r| r != 2)
+    .collect();
+  assert_eq!(vec![11, 107, 197, 1621], factors);
+
+  // A u coordinate on the twist whose order is the given product of primes.
+  let mut twist_point = |primes: &[u32]| -> BigUint {
+    let order = primes.iter().fold(BigUint::one(), |accum, &r| {
+      &accum * &BigUint::from_u64(u64::from(r))
+    });
+    let cofactor = &twist_order / &order;
+    loop {
+      let u = BigUint::random_below(&mut rng, p);
+      if curve.is_on_curve(&u) {
+        continue;
+      }
+      let h = curve.ladder(&u, &cofactor);
+      let has_order = primes.iter().all(|&r| {
+        let r_big = BigUint::from_u64(u64::from(r));
+        !curve.ladder(&h, &(&order / &r_big)).is_zero()
+      });
+      if has_order {
+        return h;
+      }
+    }
+  };
+
+  // k * h and -k * h share a u coordinate, so this only gives k up to sign.
+  let mut residues = Vec::new();
+  for &r in factors.iter() {
+    let h = twist_point(&[r]);
+    let mac = bob(&h);
+    let residue = (0..u64::from(r) / 2 + 1)
+      .map(BigUint::from_u64)
+      .find(|i| is_match(&h, i, &mac))
+      .unwrap();
+    println!("Private key is ±{} mod {}", residue, r);
+    residues.push((residue, BigUint::from_u64(u64::from(r))));
+  }
+
+  // A point whose order is the modulus so far times the next prime tells
+  // which sign of the next residue goes with the residue so far, which leaves
+  // a single sign for the whole thing.
+  let (mut n, mut modulus) = residues[0].clone();
+  for (i, (residue, r)) in residues.iter().enumerate().skip(1) {
+    let h = twist_point(&factors[..=i]);
+    let mac = bob(&h);
+    let (combined, combined_modulus) = [residue.clone(), &(r - residue) % r]
+      .iter()
+      .map(|residue| bigint::crt(&[(n.clone(), modulus.clone()), (residue.clone(), r.clone())]))
+      .find(|(k, _)| is_match(&h, k, &mac))
+      .unwrap();
+    n = combined;
+    modulus = combined_modulus;
+  }
+  println!("Private key is ±{} mod {}", n, modulus);
+
+  // Under u = x - 178, Bob's public key is ±k * G on the Weierstrass curve,
+  // so k = n' + m * modulus with n' one of ±n and small m, and
+  // (±k * G) - n' * G = m * (modulus * G) for one choice of the two signs.
+  let group = &domain.curve;
+  let x = bob_public.add_mod(&BigUint::from_u64(178), p);
+  let public_point = group.point_with_x(&x).unwrap();
+  let g_prime = group.power(&domain.generator, &modulus);
+  let bound = (&key_bound / &modulus).to_u64().unwrap();
+  let mut candidates = Vec::new();
+  for n in [n.clone(), &modulus - &n].iter() {
+    for point in [public_point.clone(), group.inverse(&public_point)].iter() {
+      candidates.push((n.clone(), point.clone()));
+    }
+  }
+  let recovered = candidates
+    .iter()
+    .filter_map(|(n, point)| {
+      let y_prime = group.combine(point, &group.inverse(&group.power(&domain.generator, n)));
+      let m = dlog::kangaroo(group, &g_prime, &y_prime, 0, bound)?;
+      Some(n + &(&BigUint::from_u64(m) * &modulus))
+    })
+    .next()
+    .unwrap();
+  assert_eq!(bob_private, recovered);
+}
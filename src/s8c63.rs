@@ -0,0 +1,67 @@
+#[test]
+fn challenge() {
+  use aes;
+  use gf128::Element;
+  use oracle;
+  use oracle::{DecodableOracle, Oracle};
+  use poly::Polynomial;
+
+  const ADDITIONAL_DATA: &[u8] = b"GET /transfer HTTP/1.1";
+  let o = oracle::AES128::with_gcm([0x42; 12], ADDITIONAL_DATA.to_vec());
+
+  let split_tag = |data: &[u8]| -> (Vec<u8>, Element) {
+    let (ciphertext, tag) = data.split_at(data.len() - aes::GCM_TAG_SIZE);
+    (ciphertext.to_vec(), Element::from_bytes(tag))
+  };
+  // tag = GHASH_H(A, C) + s where s is fixed by the key and the nonce, so the
+  // tag is a polynomial in H whose constant term is s.
+  let tag_polynomial = |ciphertext: &[u8]| -> Polynomial {
+    let mut coefficients: Vec<Element> = aes::ghash_blocks(ADDITIONAL_DATA, ciphertext)
+      .iter()
+      .map(|block| Element::from_bytes(block))
+      .collect();
+    coefficients.push(Element::zero());
+    coefficients.reverse();
+    Polynomial::new(coefficients)
+  };
+
+  let messages: Vec<&[u8]> = vec![
+    b"amount=100&to=alice",
+    b"amount=25&to=bob&memo=lunch",
+    b"amount=7&to=carol",
+  ];
+  let encrypted: Vec<(Vec<u8>, Element)> = messages
+    .iter()
+    .map(|m| split_tag(&o.encode(m).data))
+    .collect();
+
+  // Two tags under the same nonce: adding them cancels s, leaving a polynomial
+  // with H as a root. Every further pair narrows down the candidates.
+  let pair_polynomial = |a: &(Vec<u8>, Element), b: &(Vec<u8>, Element)| -> Polynomial {
+    &(&tag_polynomial(&a.0) + &tag_polynomial(&b.0)) + &Polynomial::constant(a.1 + b.1)
+  };
+  let mut candidates = pair_polynomial(&encrypted[0], &encrypted[1]).roots();
+  println!("{} candidates for H", candidates.len());
+  let second_pair = pair_polynomial(&encrypted[0], &encrypted[2]);
+  candidates.retain(|&h| second_pair.evaluate(h).is_zero());
+  assert_eq!(1, candidates.len());
+  let h = candidates[0];
+  let s = encrypted[0].1 + aes::ghash(h, ADDITIONAL_DATA, &encrypted[0].0);
+
+  // CTR is malleable, so raise the amount and sign the result ourselves.
+  let mut forged = encrypted[0].0.clone();
+  let original = b"amount=100&to=alice";
+  let wanted = b"amount=999&to=alice";
+  for i in 0..forged.len() {
+    forged[i] ^= original[i] ^ wanted[i];
+  }
+  let tag = aes::ghash(h, ADDITIONAL_DATA, &forged) + s;
+  forged.extend_from_slice(&tag.to_bytes());
+  assert_eq!(Ok(wanted.to_vec()), o.open(&forged));
+  assert_eq!(wanted.to_vec(), o.decode(&forged));
+
+  // A guessed tag is rejected.
+  let last = forged.len() - 1;
+  forged[last] ^= 1;
+  assert_eq!(Err(aes::IntegrityError::Mismatch), o.open(&forged));
+}
@@ -0,0 +1,85 @@
+pub const BLOCK_SIZE: usize = 64;
+pub const OUTPUT_SIZE: usize = 20;
+
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+fn compress(state: &mut [u32; 5], block: &[u8]) {
+  let mut w = [0u32; 80];
+  for (i, word) in block.chunks(4).enumerate() {
+    w[i] = word
+      .iter()
+      .fold(0u32, |accum, &byte| (accum << 8) | u32::from(byte));
+  }
+  for i in 16..80 {
+    w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+  }
+  let [mut a, mut b, mut c, mut d, mut e] = *state;
+  for (i, &word) in w.iter().enumerate() {
+    let (f, k) = match i {
+      0..=19 => ((b & c) | (!b & d), 0x5A827999),
+      20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+      40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+      _ => (b ^ c ^ d, 0xCA62C1D6),
+    };
+    let temp = a
+      .rotate_left(5)
+      .wrapping_add(f)
+      .wrapping_add(e)
+      .wrapping_add(k)
+      .wrapping_add(word);
+    e = d;
+    d = c;
+    c = b.rotate_left(30);
+    b = a;
+    a = temp;
+  }
+  for (s, v) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+    *s = s.wrapping_add(*v);
+  }
+}
+
+// The message followed by a 1 bit, zeros and its length in bits as a 64 bit
+// big endian integer, up to a multiple of the block size.
+pub fn pad(data: &[u8]) -> Vec<u8> {
+  let mut padded = data.to_vec();
+  padded.push(0x80);
+  while padded.len() % BLOCK_SIZE != BLOCK_SIZE - 8 {
+    padded.push(0);
+  }
+  let bit_length = (data.len() as u64).wrapping_mul(8);
+  for i in (0..8).rev() {
+    padded.push((bit_length >> (8 * i)) as u8);
+  }
+  padded
+}
+
+pub fn hash(data: &[u8]) -> Vec<u8> {
+  let mut state = INITIAL_STATE;
+  for block in pad(data).chunks(BLOCK_SIZE) {
+    compress(&mut state, block);
+  }
+  let mut digest = Vec::with_capacity(OUTPUT_SIZE);
+  for word in state.iter() {
+    for i in (0..4).rev() {
+      digest.push((word >> (8 * i)) as u8);
+    }
+  }
+  digest
+}
+
+#[test]
+fn test_vectors() {
+  use util::parse_byte_string;
+  assert_eq!(
+    parse_byte_string("da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+    hash(b"")
+  );
+  assert_eq!(
+    parse_byte_string("a9993e364706816aba3e25717850c26c9cd0d89d"),
+    hash(b"abc")
+  );
+  assert_eq!(
+    parse_byte_string("84983e441c3bd26ebaae4aa1f95129e5e54670f1"),
+    hash(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+  );
+}
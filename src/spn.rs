@@ -0,0 +1,105 @@
+use modes::BlockCipher;
+
+// The 16 bit substitution-permutation network from Heys' tutorial on linear
+// and differential cryptanalysis: four rounds of 4 bit S-boxes, with a bit
+// transposition between them. Its five round keys are the 16 bit windows of a
+// 32 bit key starting at every fourth bit, so the whole key space can be
+// searched.
+const SBOX: [u16; 16] = [
+  0xe, 0x4, 0xd, 0x1, 0x2, 0xf, 0xb, 0x8, 0x3, 0xa, 0x6, 0xc, 0x5, 0x9, 0x0, 0x7,
+];
+
+const INV_SBOX: [u16; 16] = [
+  0xe, 0x3, 0x4, 0x8, 0x1, 0xc, 0xa, 0xf, 0x7, 0xd, 0x9, 0x6, 0xb, 0x2, 0x0, 0x5,
+];
+
+const ROUNDS: usize = 4;
+
+#[derive(Clone)]
+pub struct Cipher {
+  round_keys: [u16; ROUNDS + 1],
+}
+
+fn substitute(state: u16, sbox: &[u16; 16]) -> u16 {
+  (0..4).fold(0, |accum, i| {
+    accum | (sbox[((state >> (4 * i)) & 0xf) as usize] << (4 * i))
+  })
+}
+
+// Bit j of S-box i moves to bit i of S-box j, which is its own inverse.
+fn permute(state: u16) -> u16 {
+  (0..16).fold(0, |accum, bit| {
+    let moved = (bit % 4) * 4 + bit / 4;
+    accum | (((state >> bit) & 1) << moved)
+  })
+}
+
+impl Cipher {
+  pub fn new(key: u32) -> Cipher {
+    let mut round_keys = [0; ROUNDS + 1];
+    for (round, round_key) in round_keys.iter_mut().enumerate() {
+      *round_key = (key >> (16 - 4 * round)) as u16;
+    }
+    Cipher { round_keys }
+  }
+
+  pub fn encrypt_u16(&self, block: u16) -> u16 {
+    let mut state = block;
+    for round in 0..ROUNDS {
+      state = substitute(state ^ self.round_keys[round], &SBOX);
+      if round != ROUNDS - 1 {
+        state = permute(state);
+      }
+    }
+    state ^ self.round_keys[ROUNDS]
+  }
+
+  pub fn decrypt_u16(&self, block: u16) -> u16 {
+    let mut state = block ^ self.round_keys[ROUNDS];
+    for round in (0..ROUNDS).rev() {
+      if round != ROUNDS - 1 {
+        state = permute(state);
+      }
+      state = substitute(state, &INV_SBOX) ^ self.round_keys[round];
+    }
+    state
+  }
+}
+
+impl BlockCipher for Cipher {
+  const BLOCK_SIZE: usize = 2;
+
+  fn encrypt(&self, block: &mut [u8]) {
+    let ciphertext = self.encrypt_u16((u16::from(block[0]) << 8) | u16::from(block[1]));
+    block.copy_from_slice(&[(ciphertext >> 8) as u8, ciphertext as u8]);
+  }
+
+  fn decrypt(&self, block: &mut [u8]) {
+    let plaintext = self.decrypt_u16((u16::from(block[0]) << 8) | u16::from(block[1]));
+    block.copy_from_slice(&[(plaintext >> 8) as u8, plaintext as u8]);
+  }
+}
+
+#[test]
+fn test_tables() {
+  for i in 0..16 {
+    assert_eq!(i, INV_SBOX[SBOX[i as usize] as usize]);
+  }
+  // The first bit of the second S-box moves to the second bit of the first.
+  assert_eq!(1 << 1, permute(1 << 4));
+  for bit in 0..16 {
+    assert_eq!(1 << bit, permute(permute(1 << bit)));
+  }
+}
+
+#[test]
+fn test_permutation() {
+  let cipher = Cipher::new(0x3a94_d63f);
+  let mut seen = vec![false; 1 << 16];
+  for block in 0..=0xffff {
+    let ciphertext = cipher.encrypt_u16(block);
+    assert!(!seen[ciphertext as usize]);
+    seen[ciphertext as usize] = true;
+    assert_eq!(block, cipher.decrypt_u16(ciphertext));
+  }
+}
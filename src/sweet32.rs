@@ -0,0 +1,93 @@
+use analysis;
+use modes::BlockCipher;
+use oracle::{Oracle, RepeatedSecret};
+use xor;
+
+// Two equal CBC ciphertext blocks had equal inputs to the cipher, so the xor
+// of their plaintext blocks is the xor of the ciphertext blocks before them.
+// A collision between a block of the secret and a block of the request, or
+// the final padding, gives that block of the secret away. Returns the secret
+// once every block of it has turned up.
+fn recover<C: BlockCipher>(
+  o: &RepeatedSecret<C>,
+  request: &[u8],
+  secret_length: usize,
+) -> Option<Vec<u8>> {
+  let block_size = C::BLOCK_SIZE;
+  assert!(request.len().is_multiple_of(block_size) && secret_length.is_multiple_of(block_size));
+  let ciphertext = o.encode(request).data;
+  let blocks_per_request = (request.len() + secret_length) / block_size;
+  let known_blocks = request.len() / block_size;
+  let padding_block = ciphertext.len() / block_size - 2;
+  let is_secret = |i: usize| i != padding_block && i % blocks_per_request >= known_blocks;
+  let known_block = |i: usize| {
+    if i == padding_block {
+      vec![block_size as u8; block_size]
+    } else {
+      let start = block_size * (i % blocks_per_request);
+      request[start..start + block_size].to_vec()
+    }
+  };
+
+  let mut secret = vec![None; secret_length / block_size];
+  for (i, j) in analysis::find_block_collisions(&ciphertext[block_size..], block_size) {
+    if is_secret(i) == is_secret(j) {
+      continue;
+    }
+    let (secret_index, known_index) = if is_secret(i) { (i, j) } else { (j, i) };
+    let mut block = known_block(known_index);
+    for &k in [i, j].iter() {
+      let previous = &ciphertext[block_size * k..block_size * (k + 1)];
+      xor::buffer_mut(&mut block, xor::Key::FullBuffer(previous));
+    }
+    secret[secret_index % blocks_per_request - known_blocks] = Some(block);
+  }
+  secret
+    .into_iter()
+    .collect::<Option<Vec<Vec<u8>>>>()
+    .map(|blocks| blocks.concat())
+}
+
+// GET /AAAA...AAAA HTTP/1.1\r\nCookie: with the secret cookie after it, which
+// fills whole blocks of either size.
+fn request() -> Vec<u8> {
+  let mut request = b"GET /".to_vec();
+  request.extend(vec![b'A'; 32]);
+  request.extend_from_slice(b" HTTP/1.1\r\nCookie: ");
+  assert_eq!(56, request.len());
+  request
+}
+
+// The fast variant, on 32 bit blocks. About a million blocks make a hundred or
+// so collisions, and a fifth of those pair a block of the secret with a known
+// one.
+#[test]
+fn sweet32() {
+  use rand::{thread_rng, Rng};
+  use toy32;
+  let secret = b"3f9a1c7e".to_vec();
+  let o = RepeatedSecret::new(
+    toy32::Cipher::new(thread_rng().gen()),
+    secret.clone(),
+    1 << 16,
+  );
+  assert_eq!(Some(secret), recover(&o, &request(), 8));
+}
+
+// The same attack on a 64 bit block cipher over 2^25 blocks, which takes a
+// couple of gigabytes. That is still far below the 2^32 blocks where 64 bit
+// blocks start to collide, so a collision turns up in only about one run in
+// 2^15 and nothing is recovered. Recovering the secret would take tens of
+// gigabytes of ciphertext.
+#[test]
+#[ignore]
+fn sweet64() {
+  use rand::{thread_rng, Rng};
+  use util;
+  use xtea;
+  let mut rng = thread_rng();
+  let secret = rng.gen::<[u8; 8]>().to_vec();
+  let cipher = xtea::Cipher::new(&util::gen_random_bytes(&mut rng, 16));
+  let o = RepeatedSecret::new(cipher, secret, 1 << 22);
+  assert_eq!(None, recover(&o, &request(), 8));
+}
@@ -0,0 +1,61 @@
+use modes::BlockCipher;
+
+const ROUNDS: usize = 8;
+
+// A toy eight round Feistel cipher on 4 byte blocks, with one 16 bit key per
+// round. Its blocks are small enough that CBC starts repeating them after
+// tens of thousands of blocks, rather than the billions a 64 bit cipher
+// takes, so birthday attacks on it run quickly.
+#[derive(Clone)]
+pub struct Cipher {
+  round_keys: [u16; ROUNDS],
+}
+
+fn round(half: u16, key: u16) -> u16 {
+  let x = half.wrapping_add(key);
+  x.rotate_left(5) ^ x.wrapping_mul(0x9e37) ^ (x >> 3)
+}
+
+impl Cipher {
+  pub fn new(round_keys: [u16; ROUNDS]) -> Cipher {
+    Cipher { round_keys }
+  }
+}
+
+impl BlockCipher for Cipher {
+  const BLOCK_SIZE: usize = 4;
+
+  fn encrypt(&self, block: &mut [u8]) {
+    let mut left = u16::from_be_bytes([block[0], block[1]]);
+    let mut right = u16::from_be_bytes([block[2], block[3]]);
+    for &key in self.round_keys.iter() {
+      let next_right = left ^ round(right, key);
+      left = right;
+      right = next_right;
+    }
+    block[..2].copy_from_slice(&left.to_be_bytes());
+    block[2..].copy_from_slice(&right.to_be_bytes());
+  }
+
+  fn decrypt(&self, block: &mut [u8]) {
+    let mut left = u16::from_be_bytes([block[0], block[1]]);
+    let mut right = u16::from_be_bytes([block[2], block[3]]);
+    for &key in self.round_keys.iter().rev() {
+      let previous_left = right ^ round(left, key);
+      right = left;
+      left = previous_left;
+    }
+    block[..2].copy_from_slice(&left.to_be_bytes());
+    block[2..].copy_from_slice(&right.to_be_bytes());
+  }
+}
+
+#[test]
+fn round_trip() {
+  let cipher = Cipher::new([1, 2, 3, 4, 5, 6, 7, 8]);
+  let mut block = *b"abcd";
+  cipher.encrypt(&mut block);
+  assert_ne!(*b"abcd", block);
+  cipher.decrypt(&mut block);
+  assert_eq!(*b"abcd", block);
+}
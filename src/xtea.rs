@@ -0,0 +1,86 @@
+use modes::BlockCipher;
+
+const DELTA: u32 = 0x9e37_79b9;
+const CYCLES: u32 = 32;
+
+// XTEA: a Feistel cipher on 8 byte blocks with a 128 bit key, with words read
+// big endian.
+#[derive(Clone)]
+pub struct Cipher {
+  key: [u32; 4],
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+  bytes
+    .iter()
+    .fold(0, |accum, &byte| (accum << 8) | u32::from(byte))
+}
+
+fn write_u32(n: u32, bytes: &mut [u8]) {
+  for (i, byte) in bytes.iter_mut().enumerate() {
+    *byte = (n >> (24 - 8 * i)) as u8;
+  }
+}
+
+fn mix(v: u32) -> u32 {
+  ((v << 4) ^ (v >> 5)).wrapping_add(v)
+}
+
+impl Cipher {
+  pub fn new(key: &[u8]) -> Cipher {
+    assert_eq!(16, key.len(), "XTEA keys are 16 bytes long");
+    let mut words = [0; 4];
+    for (word, chunk) in words.iter_mut().zip(key.chunks(4)) {
+      *word = read_u32(chunk);
+    }
+    Cipher { key: words }
+  }
+}
+
+impl BlockCipher for Cipher {
+  const BLOCK_SIZE: usize = 8;
+
+  fn encrypt(&self, block: &mut [u8]) {
+    let (mut v0, mut v1) = (read_u32(&block[..4]), read_u32(&block[4..]));
+    let mut sum = 0u32;
+    for _ in 0..CYCLES {
+      v0 = v0.wrapping_add(mix(v1) ^ sum.wrapping_add(self.key[(sum & 3) as usize]));
+      sum = sum.wrapping_add(DELTA);
+      v1 = v1.wrapping_add(mix(v0) ^ sum.wrapping_add(self.key[((sum >> 11) & 3) as usize]));
+    }
+    write_u32(v0, &mut block[..4]);
+    write_u32(v1, &mut block[4..]);
+  }
+
+  fn decrypt(&self, block: &mut [u8]) {
+    let (mut v0, mut v1) = (read_u32(&block[..4]), read_u32(&block[4..]));
+    let mut sum = DELTA.wrapping_mul(CYCLES);
+    for _ in 0..CYCLES {
+      v1 = v1.wrapping_sub(mix(v0) ^ sum.wrapping_add(self.key[((sum >> 11) & 3) as usize]));
+      sum = sum.wrapping_sub(DELTA);
+      v0 = v0.wrapping_sub(mix(v1) ^ sum.wrapping_add(self.key[(sum & 3) as usize]));
+    }
+    write_u32(v0, &mut block[..4]);
+    write_u32(v1, &mut block[4..]);
+  }
+}
+
+#[test]
+fn test_vectors() {
+  use util::parse_byte_string;
+  let key: Vec<u8> = (0..16).collect();
+  let vectors = [
+    (&key[..], "ABCDEFGH", "497df3d072612cb5"),
+    (&key[..], "AAAAAAAA", "e78f2d13744341d8"),
+    (&[0; 16][..], "ABCDEFGH", "a0390589f8b8efa5"),
+    (&[0; 16][..], "AAAAAAAA", "ed23375a821a8c2d"),
+  ];
+  for &(key, plaintext, ciphertext) in vectors.iter() {
+    let cipher = Cipher::new(key);
+    let mut block = plaintext.as_bytes().to_vec();
+    cipher.encrypt(&mut block);
+    assert_eq!(parse_byte_string(ciphertext), block);
+    cipher.decrypt(&mut block);
+    assert_eq!(plaintext.as_bytes(), &block[..]);
+  }
+}
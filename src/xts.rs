@@ -0,0 +1,225 @@
+use aes::{self, Operation};
+use util;
+use xor;
+
+// Multiplies the tweak by x in GF(2^128) modulo x^128 + x^7 + x^2 + x + 1.
+// Unlike GCM, XTS reads the tweak as a little endian number, so that the
+// first bit of the first byte is the coefficient of x^0.
+fn multiply_by_alpha(tweak: &mut [u8; 16]) {
+  let mut carry = 0;
+  for byte in tweak.iter_mut() {
+    let next_carry = *byte >> 7;
+    *byte = (*byte << 1) | carry;
+    carry = next_carry;
+  }
+  if carry == 1 {
+    tweak[0] ^= 0x87;
+  }
+}
+
+// AES-XTS as used for disk encryption: each sector is encrypted on its own
+// under a tweak derived from its number, so that equal sectors at different
+// positions look unrelated. A final partial block is handled with ciphertext
+// stealing, leaving the ciphertext as long as the plaintext.
+#[derive(Clone)]
+pub struct Cipher {
+  data_cipher: aes::Cipher,
+  tweak_cipher: aes::Cipher,
+}
+
+impl Cipher {
+  // Takes the data key followed by the tweak key.
+  pub fn new(key: &[u8]) -> Cipher {
+    assert!(
+      key.len() == 32 || key.len() == 64,
+      "XTS needs two AES-128 or AES-256 keys, got {} bytes",
+      key.len()
+    );
+    let (data_key, tweak_key) = key.split_at(key.len() / 2);
+    Cipher {
+      data_cipher: aes::Cipher::new(data_key),
+      tweak_cipher: aes::Cipher::new(tweak_key),
+    }
+  }
+
+  pub fn encrypt_sector(&self, sector: u64, data: &[u8]) -> Vec<u8> {
+    self.transform_sector(sector, data, Operation::Encrypt)
+  }
+
+  pub fn decrypt_sector(&self, sector: u64, data: &[u8]) -> Vec<u8> {
+    self.transform_sector(sector, data, Operation::Decrypt)
+  }
+
+  fn transform_block(&self, block: &mut [u8], tweak: &[u8; 16], operation: Operation) {
+    let mut state = util::convert_to_fixed_array(block);
+    xor::buffer_mut(&mut state, xor::Key::FullBuffer(tweak));
+    let mut state = match operation {
+      Operation::Encrypt => self.data_cipher.encrypt_block(&state),
+      Operation::Decrypt => self.data_cipher.decrypt_block(&state),
+    };
+    xor::buffer_mut(&mut state, xor::Key::FullBuffer(tweak));
+    block.copy_from_slice(&state);
+  }
+
+  fn transform_sector(&self, sector: u64, data: &[u8], operation: Operation) -> Vec<u8> {
+    assert!(
+      data.len() >= 16,
+      "Sector of {} bytes is shorter than a block",
+      data.len()
+    );
+    let mut sector_block = [0; 16];
+    sector_block[..8].copy_from_slice(&sector.to_le_bytes());
+    let mut tweak = self.tweak_cipher.encrypt_block(&sector_block);
+    let full_blocks = data.len() / 16;
+    let remainder = data.len() % 16;
+    let mut tweaks = Vec::with_capacity(full_blocks + 1);
+    for _ in 0..=full_blocks {
+      tweaks.push(tweak);
+      multiply_by_alpha(&mut tweak);
+    }
+
+    // With a partial final block, encryption uses the last two tweaks on the
+    // last whole block and then the stolen one, and decryption has to undo
+    // them in the opposite order.
+    let (last_whole_tweak, stolen_tweak) = match operation {
+      Operation::Encrypt => (full_blocks - 1, full_blocks),
+      Operation::Decrypt => (full_blocks, full_blocks - 1),
+    };
+    let mut output = data.to_vec();
+    for (i, block) in output.chunks_mut(16).take(full_blocks).enumerate() {
+      let tweak = if remainder != 0 && i == full_blocks - 1 {
+        &tweaks[last_whole_tweak]
+      } else {
+        &tweaks[i]
+      };
+      self.transform_block(block, tweak, operation);
+    }
+
+    if remainder != 0 {
+      // The partial block borrows the tail of the last whole block's output,
+      // and takes its place, while the head of that output becomes the final
+      // partial block.
+      let last_whole = 16 * (full_blocks - 1);
+      let partial = 16 * full_blocks;
+      let mut block = [0; 16];
+      block[..remainder].copy_from_slice(&data[partial..]);
+      block[remainder..].copy_from_slice(&output[last_whole + remainder..partial]);
+      self.transform_block(&mut block, &tweaks[stolen_tweak], operation);
+      let (head, tail) = output.split_at_mut(partial);
+      tail.copy_from_slice(&head[last_whole..last_whole + remainder]);
+      head[last_whole..].copy_from_slice(&block);
+    }
+    output
+  }
+}
+
+#[test]
+fn test_multiply_by_alpha() {
+  let mut tweak = [0; 16];
+  tweak[0] = 0x80;
+  multiply_by_alpha(&mut tweak);
+  assert_eq!([0, 1], tweak[..2]);
+  let mut tweak = [0; 16];
+  tweak[15] = 0x80;
+  multiply_by_alpha(&mut tweak);
+  assert_eq!(0x87, tweak[0]);
+  assert_eq!([0; 15], tweak[1..]);
+}
+
+#[test]
+fn ieee_1619_vectors() {
+  use util::parse_byte_string;
+  let stealing_key = "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0";
+  let stealing_plaintext = "000102030405060708090a0b0c0d0e0f10111213";
+  let vectors = [
+    (
+      "0000000000000000000000000000000000000000000000000000000000000000",
+      0,
+      "0000000000000000000000000000000000000000000000000000000000000000",
+      "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e",
+    ),
+    (
+      "1111111111111111111111111111111122222222222222222222222222222222",
+      0x33_3333_3333,
+      "4444444444444444444444444444444444444444444444444444444444444444",
+      "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0",
+    ),
+    (
+      stealing_key,
+      0x12_3456_789a,
+      &stealing_plaintext[..34],
+      "6c1625db4671522d3d7599601de7ca09ed",
+    ),
+    (
+      stealing_key,
+      0x12_3456_789a,
+      &stealing_plaintext[..36],
+      "d069444b7a7e0cab09e24447d24deb1fedbf",
+    ),
+    (
+      stealing_key,
+      0x12_3456_789a,
+      &stealing_plaintext[..38],
+      "e5df1351c0544ba1350b3363cd8ef4beedbf9d",
+    ),
+    (
+      stealing_key,
+      0x12_3456_789a,
+      stealing_plaintext,
+      "9d84c813f719aa2c7be3f66171c7c5c2edbf9dac",
+    ),
+  ];
+  for &(key, sector, plaintext, ciphertext) in vectors.iter() {
+    let cipher = Cipher::new(&parse_byte_string(key));
+    let plaintext = parse_byte_string(plaintext);
+    let ciphertext = parse_byte_string(ciphertext);
+    assert_eq!(ciphertext, cipher.encrypt_sector(sector, &plaintext));
+    assert_eq!(plaintext, cipher.decrypt_sector(sector, &ciphertext));
+  }
+}
+
+#[test]
+fn disk_sector_attacks() {
+  use oracle::Disk;
+  let mut disk = Disk::new(4);
+  let secret = vec![b'A'; Disk::SECTOR_SIZE];
+  let mut update = secret.clone();
+  update[100] = b'B';
+  update[500] = b'C';
+
+  // The same plaintext in two sectors, or in two blocks of one sector,
+  // encrypts differently, so nothing leaks like it would with ECB.
+  disk.write(0, &secret);
+  disk.write(1, &secret);
+  let raw = disk.raw_sector(0);
+  assert_ne!(raw, disk.raw_sector(1));
+  assert_ne!(raw[..16], raw[16..32]);
+
+  // Moving a sector somewhere else only yields garbage.
+  disk.set_raw_sector(2, &raw);
+  assert_ne!(secret, disk.read(2));
+
+  // A flipped bit scrambles the block it's in and nothing else, so there is
+  // no way to make a targeted change.
+  let mut flipped = raw.clone();
+  flipped[40] ^= 1;
+  disk.set_raw_sector(0, &flipped);
+  let read = disk.read(0);
+  assert_ne!(secret[32..48], read[32..48]);
+  assert_eq!(secret[..32], read[..32]);
+  assert_eq!(secret[48..], read[48..]);
+
+  // But nothing stops an old version of a sector from being put back in
+  // place, and since every block stands on its own, the rollback can even be
+  // limited to a single block.
+  disk.write(0, &update);
+  let updated_raw = disk.raw_sector(0);
+  disk.set_raw_sector(0, &raw);
+  assert_eq!(secret, disk.read(0));
+  let mut spliced = updated_raw.clone();
+  spliced[96..112].copy_from_slice(&raw[96..112]);
+  disk.set_raw_sector(0, &spliced);
+  let read = disk.read(0);
+  assert_eq!(b'A', read[100]);
+  assert_eq!(b'C', read[500]);
+}